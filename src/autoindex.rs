@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+
+use crate::{name_from_url, Naming};
+
+/// Extracts name-to-URL pairs from the links of a directory listing page,
+/// such as an Apache or nginx autoindex, fetched from `base`.
+///
+/// Only links to files directly in the listed directory are taken, leaving out
+/// subdirectories, sorting links and links elsewhere.
+pub fn parse(body: &[u8], base: &str, naming: Naming) -> HashMap<String, String> {
+    let body = String::from_utf8_lossy(body);
+    // Links are resolved against the directory, not the page.
+    let dir = &base[..base.rfind('/').map_or(base.len(), |i| i + 1)];
+    let origin = match base.split_once("://") {
+        Some((scheme, rest)) => {
+            let authority = rest.split(['/', '?', '#']).next().unwrap();
+            format!("{scheme}://{authority}")
+        }
+        None => String::new(),
+    };
+
+    let links = Regex::new(r#"(?i)\bhref\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap();
+    let mut map = HashMap::new();
+    for caps in links.captures_iter(&body) {
+        let href = caps.get(1).or(caps.get(2)).unwrap().as_str();
+        // Fragments only point into the page a link leads to.
+        let href = href.split('#').next().unwrap().replace("&amp;", "&");
+        if href.is_empty() || href.starts_with('?') {
+            continue;
+        }
+        let url = if href.contains("://") {
+            href
+        } else if let Some(path) = href.strip_prefix('/') {
+            format!("{origin}/{path}")
+        } else {
+            format!("{dir}{}", href.strip_prefix("./").unwrap_or(&href))
+        };
+        let Some(rest) = url.strip_prefix(dir) else {
+            continue;
+        };
+        let path = rest.split('?').next().unwrap();
+        if path.is_empty() || path.contains('/') || path == ".." {
+            continue;
+        }
+        map.insert(name_from_url(&url, naming).into(), url);
+    }
+    map
+}
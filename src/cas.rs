@@ -0,0 +1,202 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::UNIX_EPOCH,
+};
+
+use sha2::{Digest, Sha224, Sha256, Sha384, Sha512};
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::s3::hex;
+
+/// Hash algorithms a content address can be computed with.
+#[derive(Clone, Copy)]
+pub enum Algo {
+    Sha224,
+    Sha256,
+    Sha384,
+    Sha512,
+    /// 64-bit XXH3, which is fast but only detects accidental corruption.
+    Xxh3,
+}
+
+impl FromStr for Algo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        Ok(match s {
+            "sha224" => Self::Sha224,
+            "sha256" => Self::Sha256,
+            "sha384" => Self::Sha384,
+            "sha512" => Self::Sha512,
+            "xxh3" => Self::Xxh3,
+            _ => return Err(format!("unsupported hash algorithm `{s}`")),
+        })
+    }
+}
+
+impl Algo {
+    /// Infers the algorithm from the number of hex digits in a digest.
+    ///
+    /// XXH3 is never inferred, as its digest is too short to tell from other names.
+    fn from_hex_len(len: usize) -> Option<Self> {
+        Some(match len {
+            56 => Self::Sha224,
+            64 => Self::Sha256,
+            96 => Self::Sha384,
+            128 => Self::Sha512,
+            _ => return None,
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Sha224 => "sha224",
+            Self::Sha256 => "sha256",
+            Self::Sha384 => "sha384",
+            Self::Sha512 => "sha512",
+            Self::Xxh3 => "xxh3",
+        }
+    }
+
+    pub fn hex_len(self) -> usize {
+        match self {
+            Self::Sha224 => 56,
+            Self::Sha256 => 64,
+            Self::Sha384 => 96,
+            Self::Sha512 => 128,
+            Self::Xxh3 => 16,
+        }
+    }
+
+    pub fn hasher(self) -> Hasher {
+        match self {
+            Self::Sha224 => Hasher::Sha224(Sha224::new()),
+            Self::Sha256 => Hasher::Sha256(Sha256::new()),
+            Self::Sha384 => Hasher::Sha384(Sha384::new()),
+            Self::Sha512 => Hasher::Sha512(Sha512::new()),
+            Self::Xxh3 => Hasher::Xxh3(Box::new(Xxh3::new())),
+        }
+    }
+
+    fn digest_file(self, path: &Path) -> io::Result<Vec<u8>> {
+        let mut hasher = self.hasher();
+        io::copy(&mut File::open(path)?, &mut hasher)?;
+        Ok(hasher.finish())
+    }
+}
+
+/// Computes a digest incrementally, e.g. over a download as it is received.
+pub enum Hasher {
+    Sha224(Sha224),
+    Sha256(Sha256),
+    Sha384(Sha384),
+    Sha512(Sha512),
+    Xxh3(Box<Xxh3>),
+}
+
+impl Hasher {
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha224(hasher) => hasher.update(data),
+            Self::Sha256(hasher) => hasher.update(data),
+            Self::Sha384(hasher) => hasher.update(data),
+            Self::Sha512(hasher) => hasher.update(data),
+            Self::Xxh3(hasher) => hasher.update(data),
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        match self {
+            Self::Sha224(hasher) => hasher.finalize().to_vec(),
+            Self::Sha256(hasher) => hasher.finalize().to_vec(),
+            Self::Sha384(hasher) => hasher.finalize().to_vec(),
+            Self::Sha512(hasher) => hasher.finalize().to_vec(),
+            Self::Xxh3(hasher) => hasher.digest().to_be_bytes().to_vec(),
+        }
+    }
+
+    pub fn finish_hex(self) -> String {
+        hex(&self.finish())
+    }
+}
+
+impl Write for Hasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Caches digests of local files in a directory, keyed on their size and modification time.
+pub struct HashCache {
+    dir: PathBuf,
+}
+
+impl HashCache {
+    pub fn new(dir: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Returns the path of the cache entry for a file, named after a hash of its
+    /// canonical path so that files with the same name in different directories don't clash.
+    fn entry(&self, path: &Path, algo: Algo) -> io::Result<PathBuf> {
+        let path = fs::canonicalize(path)?;
+        let key = hex(&Sha256::digest(path.to_string_lossy().as_bytes())[..16]);
+        Ok(self.dir.join(format!("{key}.{}", algo.name())))
+    }
+}
+
+/// Returns the hex digest of a file, reusing the one in `cache` if the file is unchanged.
+pub fn hex_digest(algo: Algo, path: &Path, cache: Option<&HashCache>) -> io::Result<String> {
+    let Some(cache) = cache else {
+        return Ok(hex(&algo.digest_file(path)?));
+    };
+    let meta = fs::metadata(path)?;
+    let mtime = meta
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    let stamp = format!("{} {mtime}", meta.len());
+
+    let entry = cache.entry(path, algo)?;
+    if let Ok(cached) = fs::read_to_string(&entry) {
+        if let Some((cached_stamp, digest)) = cached.trim_end().rsplit_once(' ') {
+            if cached_stamp == stamp {
+                return Ok(digest.into());
+            }
+        }
+    }
+    let digest = hex(&algo.digest_file(path)?);
+    fs::write(entry, format!("{stamp} {digest}\n"))?;
+    Ok(digest)
+}
+
+/// Checks that the content of a file hashes to the hex digest its name starts with.
+///
+/// With no algorithm given, the whole digest must be present for the algorithm
+/// to be inferred from its length; otherwise a prefix of the digest suffices.
+/// Returns `None` if the name holds no digest that can be checked.
+pub fn verify(
+    path: &Path,
+    name: &str,
+    algo: Option<Algo>,
+    cache: Option<&HashCache>,
+) -> io::Result<Option<bool>> {
+    let len = name.bytes().take_while(u8::is_ascii_hexdigit).count();
+    let Some(algo) = algo.or_else(|| Algo::from_hex_len(len)) else {
+        return Ok(None);
+    };
+    if len == 0 || len > algo.hex_len() {
+        return Ok(None);
+    }
+    let digest = hex_digest(algo, path, cache)?;
+    Ok(Some(digest.starts_with(&name[..len].to_ascii_lowercase())))
+}
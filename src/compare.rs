@@ -0,0 +1,121 @@
+use std::{
+    io::{self, Read},
+    rc::Rc,
+};
+
+use crate::plugin::Plugin;
+
+/// Compares a stream of received bytes with the content of a local file.
+pub struct Comparator {
+    local: Box<dyn Read>,
+    /// Decides the equality of bytes instead of comparing them exactly.
+    plugin: Option<Rc<Plugin>>,
+    buf: Box<[u8; 16384]>,
+    /// Number of bytes received so far.
+    pub received: u64,
+    first_diff: Option<u64>,
+    ended: bool,
+    /// Whether the longer side has only had zeros since the shorter one ended.
+    tail_zeros: bool,
+}
+
+impl Comparator {
+    pub fn new(local: Box<dyn Read>, plugin: Option<Rc<Plugin>>) -> Self {
+        Self {
+            local,
+            plugin,
+            buf: Box::new([0; 16384]),
+            received: 0,
+            first_diff: None,
+            ended: false,
+            tail_zeros: true,
+        }
+    }
+
+    /// Compares the next received bytes with the local file.
+    pub fn update(&mut self, data: &[u8]) {
+        for chunk in data.chunks(self.buf.len()) {
+            if self.first_diff.is_none() {
+                let buf = &mut self.buf[..chunk.len()];
+                let offset = match read_full(&mut self.local, buf) {
+                    Ok(n) => {
+                        let pos = match &self.plugin {
+                            Some(plugin) => plugin.compare(&buf[..n], chunk, self.received),
+                            None => chunk.iter().zip(&buf[..n]).position(|(a, b)| a != b),
+                        };
+                        self.ended = pos.is_none() && n < chunk.len();
+                        if self.ended {
+                            self.tail_zeros = is_zeros(&chunk[n..]);
+                        }
+                        pos.or(self.ended.then_some(n))
+                    }
+                    Err(_) => Some(0),
+                };
+                self.first_diff = offset.map(|i| self.received + i as u64);
+            } else if self.ended && self.tail_zeros {
+                self.tail_zeros = is_zeros(chunk);
+            }
+            self.received += chunk.len() as u64;
+        }
+    }
+
+    /// Returns the offset of the first differing byte after all bytes are received,
+    /// or `None` if the local file has the same content.
+    pub fn finish(&mut self) -> Option<u64> {
+        let mut next = [0];
+        if self.first_diff.is_none() && !matches!(read_full(&mut self.local, &mut next), Ok(0)) {
+            self.first_diff = Some(self.received);
+            self.ended = true;
+            self.tail_zeros = next[0] == 0;
+        }
+        self.first_diff
+    }
+
+    /// Returns whether the contents only differ in that one side ended before the other.
+    pub fn ended_early(&self) -> bool {
+        self.ended
+    }
+
+    /// Returns whether the contents only differ in that the longer side goes on with zeros,
+    /// reading the rest of the local file if it is the longer one.
+    ///
+    /// This is only meaningful after [`Self::finish`].
+    pub fn only_padding(&mut self) -> bool {
+        if !self.ended || !self.tail_zeros {
+            return false;
+        }
+        if self.local_is_prefix() {
+            return true;
+        }
+        loop {
+            match read_full(&mut self.local, &mut self.buf[..]) {
+                Ok(0) => return true,
+                Ok(n) if is_zeros(&self.buf[..n]) => {}
+                _ => return false,
+            }
+        }
+    }
+
+    /// Returns whether the local file ended before the received bytes, having matched them until then.
+    pub fn local_is_prefix(&self) -> bool {
+        self.ended && self.first_diff.is_some_and(|offset| offset < self.received)
+    }
+}
+
+fn is_zeros(data: &[u8]) -> bool {
+    data.iter().all(|&b| b == 0)
+}
+
+/// Reads until `buf` is full or the end of the reader is reached.
+fn read_full(reader: &mut dyn Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match reader.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(m) => n += m,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(n)
+}
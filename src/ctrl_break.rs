@@ -0,0 +1,40 @@
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use windows_sys::Win32::{
+    Foundation::BOOL,
+    System::Console::{SetConsoleCtrlHandler, CTRL_BREAK_EVENT},
+};
+
+/// Flags to set on Ctrl+Break, one for each registration in the process.
+static FLAGS: Mutex<Vec<Arc<AtomicBool>>> = Mutex::new(Vec::new());
+
+/// Handles Ctrl+Break by setting the flags, returning nonzero to keep the process running.
+/// Other events are passed on to the default handler.
+unsafe extern "system" fn handler(ctrl_type: u32) -> BOOL {
+    if ctrl_type != CTRL_BREAK_EVENT {
+        return 0;
+    }
+    for flag in FLAGS.lock().unwrap().iter() {
+        flag.store(true, Ordering::Relaxed);
+    }
+    1
+}
+
+/// Sets `flag` on Ctrl+Break in the console, or when the event is sent with
+/// `GenerateConsoleCtrlEvent`, instead of terminating the process,
+/// as SIGUSR1 does with `signal_hook::flag::register` on Unix.
+pub fn register(flag: Arc<AtomicBool>) -> io::Result<()> {
+    let mut flags = FLAGS.lock().unwrap();
+    // Console control handlers are called on a thread of their own, which takes the lock.
+    if flags.is_empty() && unsafe { SetConsoleCtrlHandler(Some(handler), 1) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    flags.push(flag);
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use std::{str::FromStr, time::Duration};
+
+use curl::easy::{Easy, HttpVersion};
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Bool,
+    Int,
+    Secs,
+    Str,
+    Version,
+}
+
+/// Supported options, named after the `Easy` setters they map onto.
+const OPTIONS: &[(&str, Kind)] = &[
+    ("accept_encoding", Kind::Str),
+    ("buffer_size", Kind::Int),
+    ("cainfo", Kind::Str),
+    ("capath", Kind::Str),
+    ("connect_timeout", Kind::Secs),
+    ("dns_servers", Kind::Str),
+    ("http_version", Kind::Version),
+    ("interface", Kind::Str),
+    ("low_speed_limit", Kind::Int),
+    ("low_speed_time", Kind::Secs),
+    ("max_recv_speed", Kind::Int),
+    ("max_redirections", Kind::Int),
+    ("noproxy", Kind::Str),
+    ("pipewait", Kind::Bool),
+    ("proxy", Kind::Str),
+    ("ssl_verify_host", Kind::Bool),
+    ("ssl_verify_peer", Kind::Bool),
+    ("tcp_keepalive", Kind::Bool),
+    ("timeout", Kind::Secs),
+    ("useragent", Kind::Str),
+    ("verbose", Kind::Bool),
+];
+
+#[derive(Clone)]
+enum Value {
+    Bool(bool),
+    Int(u64),
+    Str(String),
+    Version(HttpVersion),
+}
+
+/// A libcurl option given as `KEY=VALUE`, with the value coerced to the type of the option.
+#[derive(Clone)]
+pub struct CurlOpt {
+    key: &'static str,
+    value: Value,
+}
+
+impl FromStr for CurlOpt {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (key, value) = s.split_once('=').ok_or("expected KEY=VALUE")?;
+        let &(key, kind) = OPTIONS
+            .iter()
+            .find(|(k, _)| *k == key)
+            .ok_or_else(|| format!("unknown curl option `{key}`"))?;
+        let value = match kind {
+            Kind::Bool => Value::Bool(match value {
+                "1" | "true" | "yes" | "on" => true,
+                "0" | "false" | "no" | "off" => false,
+                _ => return Err(format!("expected boolean for `{key}`")),
+            }),
+            Kind::Int | Kind::Secs => Value::Int(
+                value
+                    .parse()
+                    .map_err(|_| format!("expected integer for `{key}`"))?,
+            ),
+            Kind::Str => Value::Str(value.into()),
+            Kind::Version => Value::Version(match value {
+                "any" => HttpVersion::Any,
+                "1.0" => HttpVersion::V10,
+                "1.1" => HttpVersion::V11,
+                "2" => HttpVersion::V2,
+                "2tls" => HttpVersion::V2TLS,
+                "2-prior-knowledge" => HttpVersion::V2PriorKnowledge,
+                "3" => HttpVersion::V3,
+                _ => return Err(format!("unknown HTTP version `{value}`")),
+            }),
+        };
+        Ok(Self { key, value })
+    }
+}
+
+impl CurlOpt {
+    /// Returns the value given to `verbose`, if this option sets it.
+    pub fn verbose(&self) -> Option<bool> {
+        match (self.key, &self.value) {
+            ("verbose", Value::Bool(b)) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, handle: &mut Easy) -> Result<(), curl::Error> {
+        match (self.key, &self.value) {
+            ("accept_encoding", Value::Str(s)) => handle.accept_encoding(s),
+            ("buffer_size", Value::Int(n)) => handle.buffer_size(*n as usize),
+            ("cainfo", Value::Str(s)) => handle.cainfo(s),
+            ("capath", Value::Str(s)) => handle.capath(s),
+            ("connect_timeout", Value::Int(n)) => handle.connect_timeout(Duration::from_secs(*n)),
+            ("dns_servers", Value::Str(s)) => handle.dns_servers(s),
+            ("http_version", Value::Version(v)) => handle.http_version(*v),
+            ("interface", Value::Str(s)) => handle.interface(s),
+            ("low_speed_limit", Value::Int(n)) => handle.low_speed_limit(*n as u32),
+            ("low_speed_time", Value::Int(n)) => handle.low_speed_time(Duration::from_secs(*n)),
+            ("max_recv_speed", Value::Int(n)) => handle.max_recv_speed(*n),
+            ("max_redirections", Value::Int(n)) => handle.max_redirections(*n as u32),
+            ("noproxy", Value::Str(s)) => handle.noproxy(s),
+            ("pipewait", Value::Bool(b)) => handle.pipewait(*b),
+            ("proxy", Value::Str(s)) => handle.proxy(s),
+            ("ssl_verify_host", Value::Bool(b)) => handle.ssl_verify_host(*b),
+            ("ssl_verify_peer", Value::Bool(b)) => handle.ssl_verify_peer(*b),
+            ("tcp_keepalive", Value::Bool(b)) => handle.tcp_keepalive(*b),
+            ("timeout", Value::Int(n)) => handle.timeout(Duration::from_secs(*n)),
+            ("useragent", Value::Str(s)) => handle.useragent(s),
+            ("verbose", Value::Bool(b)) => handle.verbose(*b),
+            _ => unreachable!(),
+        }
+    }
+}
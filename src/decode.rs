@@ -0,0 +1,222 @@
+use std::str::FromStr;
+
+/// Encodings a remote file may be served in, to be decoded before comparison.
+#[derive(Clone, Copy)]
+pub enum Encoding {
+    /// Standard or URL-safe Base64, with or without padding.
+    Base64,
+    Hex,
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        Ok(match s {
+            "base64" => Self::Base64,
+            "hex" => Self::Hex,
+            _ => return Err(format!("unsupported encoding `{s}`")),
+        })
+    }
+}
+
+impl Encoding {
+    /// Returns the status of a remote file that isn't validly encoded.
+    pub fn invalid_status(self) -> &'static str {
+        match self {
+            Self::Base64 => "bad: invalid base64",
+            Self::Hex => "bad: invalid hex",
+        }
+    }
+
+    fn group_len(self) -> usize {
+        match self {
+            Self::Base64 => 4,
+            Self::Hex => 2,
+        }
+    }
+
+    fn value(self, c: u8) -> Option<u8> {
+        match self {
+            Self::Base64 => match c {
+                b'A'..=b'Z' => Some(c - b'A'),
+                b'a'..=b'z' => Some(c - b'a' + 26),
+                b'0'..=b'9' => Some(c - b'0' + 52),
+                b'+' | b'-' => Some(62),
+                b'/' | b'_' => Some(63),
+                _ => None,
+            },
+            Self::Hex => (c as char).to_digit(16).map(|d| d as u8),
+        }
+    }
+}
+
+/// Decodes a stream of encoded bytes received in chunks of any size.
+///
+/// Whitespace is skipped, so that line-wrapped encodings decode as well, and
+/// the characters of a group split across chunks are carried over to the next one.
+pub struct Decoder {
+    encoding: Encoding,
+    /// Values of the characters of the incomplete group.
+    group: [u8; 4],
+    len: usize,
+    /// Number of `=` characters ending the last group, after which only whitespace may follow.
+    padding: usize,
+    invalid: bool,
+}
+
+impl Decoder {
+    pub fn new(encoding: Encoding) -> Self {
+        Self {
+            encoding,
+            group: [0; 4],
+            len: 0,
+            padding: 0,
+            invalid: false,
+        }
+    }
+
+    /// Decodes the next received bytes, appending them to `out`.
+    pub fn update(&mut self, data: &[u8], out: &mut Vec<u8>) {
+        for &c in data {
+            if self.invalid {
+                return;
+            }
+            if c.is_ascii_whitespace() {
+                continue;
+            }
+            if c == b'=' && matches!(self.encoding, Encoding::Base64) {
+                self.padding += 1;
+                // Padding ends a group of two or three characters.
+                if self.len + self.padding > 4 || self.len < 2 {
+                    self.invalid = true;
+                } else if self.len + self.padding == 4 {
+                    self.flush(out);
+                }
+                continue;
+            }
+            match self.encoding.value(c) {
+                Some(v) if self.padding == 0 => {
+                    self.group[self.len] = v;
+                    self.len += 1;
+                    if self.len == self.encoding.group_len() {
+                        self.flush(out);
+                    }
+                }
+                _ => self.invalid = true,
+            }
+        }
+    }
+
+    /// Decodes the incomplete group ending the stream into `out`,
+    /// returning whether the whole stream was validly encoded.
+    pub fn finish(&mut self, out: &mut Vec<u8>) -> bool {
+        match self.encoding {
+            // Padding may be left out.
+            Encoding::Base64 if self.padding == 0 && self.len >= 2 => self.flush(out),
+            _ if self.len != 0 => self.invalid = true,
+            _ => {}
+        }
+        !self.invalid
+    }
+
+    fn flush(&mut self, out: &mut Vec<u8>) {
+        let g = self.group;
+        match self.encoding {
+            Encoding::Base64 => {
+                let bytes = [
+                    g[0] << 2 | g[1] >> 4,
+                    g[1] << 4 | g[2] >> 2,
+                    g[2] << 6 | g[3],
+                ];
+                out.extend_from_slice(&bytes[..self.len - 1]);
+            }
+            Encoding::Hex => out.push(g[0] << 4 | g[1]),
+        }
+        self.group = [0; 4];
+        self.len = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decodes a stream received in the given chunks, or returns `None` if it is invalid.
+    fn decode(encoding: Encoding, chunks: &[&str]) -> Option<Vec<u8>> {
+        let mut decoder = Decoder::new(encoding);
+        let mut out = Vec::new();
+        for chunk in chunks {
+            decoder.update(chunk.as_bytes(), &mut out);
+        }
+        decoder.finish(&mut out).then_some(out)
+    }
+
+    #[test]
+    fn groups_split_across_chunks() {
+        let whole = decode(Encoding::Base64, &["aGVsbG8gd29ybGQ="]);
+        assert_eq!(whole.as_deref(), Some(&b"hello world"[..]));
+        for at in 1..16 {
+            let (a, b) = "aGVsbG8gd29ybGQ=".split_at(at);
+            assert_eq!(decode(Encoding::Base64, &[a, b]), whole, "split at {at}");
+        }
+        let bytes: Vec<_> = "68656c6c6f".chars().map(String::from).collect();
+        let chunks: Vec<_> = bytes.iter().map(String::as_str).collect();
+        assert_eq!(
+            decode(Encoding::Hex, &chunks).as_deref(),
+            Some(&b"hello"[..])
+        );
+        assert_eq!(
+            decode(Encoding::Hex, &["686", "56c6c6f"]).as_deref(),
+            Some(&b"hello"[..])
+        );
+    }
+
+    #[test]
+    fn whitespace_inside_groups() {
+        assert_eq!(
+            decode(Encoding::Base64, &["aGV\r\nsbG", "8g d2\t9y", "\nbGQ=\n"]).as_deref(),
+            Some(&b"hello world"[..])
+        );
+        assert_eq!(
+            decode(Encoding::Hex, &["6 8", "6\n5"]).as_deref(),
+            Some(&b"he"[..])
+        );
+    }
+
+    #[test]
+    fn url_safe_and_unpadded() {
+        // `+/` and `-_` encode the same values.
+        assert_eq!(
+            decode(Encoding::Base64, &["-_8="]),
+            decode(Encoding::Base64, &["+/8="])
+        );
+        assert_eq!(
+            decode(Encoding::Base64, &["-_8="]).as_deref(),
+            Some(&[0xfb, 0xff][..])
+        );
+        assert_eq!(
+            decode(Encoding::Base64, &["YQ"]).as_deref(),
+            Some(&b"a"[..])
+        );
+        assert_eq!(
+            decode(Encoding::Base64, &["Y", "WI"]).as_deref(),
+            Some(&b"ab"[..])
+        );
+        assert_eq!(decode(Encoding::Base64, &["YQ", "="]), None);
+        assert_eq!(
+            decode(Encoding::Base64, &["YQ=", "="]).as_deref(),
+            Some(&b"a"[..])
+        );
+    }
+
+    #[test]
+    fn ending_mid_group_is_invalid() {
+        assert_eq!(decode(Encoding::Base64, &["YWJj", "Z"]), None);
+        assert_eq!(decode(Encoding::Hex, &["686", "\n"]), None);
+        // Nothing may follow padding.
+        assert_eq!(decode(Encoding::Base64, &["YQ==", "YQ=="]), None);
+        assert_eq!(Encoding::Base64.invalid_status(), "bad: invalid base64");
+        assert_eq!(Encoding::Hex.invalid_status(), "bad: invalid hex");
+    }
+}
@@ -0,0 +1,69 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+};
+
+use serde_json::json;
+
+use crate::status_kind;
+
+/// Reads a record into a map from names to their last recorded status.
+///
+/// Files still marked as being checked keep the status they had before.
+fn read_rec(reader: impl BufRead) -> io::Result<HashMap<String, String>> {
+    let mut map = HashMap::new();
+    for line in reader.lines() {
+        if let Some((name, status)) = line?
+            .split_once(": ")
+            .filter(|(_, status)| status_kind(status) != "checking")
+        {
+            map.insert(name.into(), status.into());
+        }
+    }
+    Ok(map)
+}
+
+/// Prints the names whose kind of status differs between two record files.
+pub fn run(old: &Path, new: &Path, json: bool) -> io::Result<()> {
+    let read = |path| read_rec(BufReader::new(File::open(path)?));
+    let (old, new) = (read(old)?, read(new)?);
+    let names: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+
+    let mut changes = Vec::new();
+    for name in names {
+        let (old, new) = (old.get(name), new.get(name));
+        if old.map(|s| status_kind(s)) != new.map(|s| status_kind(s)) {
+            changes.push((name, old, new));
+        }
+    }
+
+    if json {
+        let changes: Vec<_> = changes
+            .into_iter()
+            .map(|(name, old, new)| json!({ "name": name, "old": old, "new": new }))
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&changes)?);
+    } else {
+        for (name, old, new) in changes {
+            let old = old.map_or("none", |s| &s[..]);
+            let new = new.map_or("none", |s| &s[..]);
+            println!("{name}: {old} -> {new}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_rec_skips_checking() {
+        let rec = "a: bad\nb: checking\na: good\na: checking\n";
+        let map = read_rec(rec.as_bytes()).unwrap();
+        assert_eq!(map.len(), 1);
+        assert_eq!(map["a"], "good");
+    }
+}
@@ -0,0 +1,55 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Number of recent files the average time per file is taken over.
+const WINDOW: usize = 20;
+
+/// Estimates the time left from the number of files left, for when their sizes are unknown.
+pub struct Eta {
+    times: VecDeque<Duration>,
+    last: Option<Instant>,
+    left: u32,
+}
+
+impl Eta {
+    pub fn new(files: u32) -> Self {
+        Self {
+            times: VecDeque::with_capacity(WINDOW),
+            last: None,
+            left: files,
+        }
+    }
+
+    /// Marks the start of a file, counting the time since the previous one.
+    pub fn start_file(&mut self) {
+        let now = Instant::now();
+        if let Some(last) = self.last.replace(now) {
+            if self.times.len() == WINDOW {
+                self.times.pop_front();
+            }
+            self.times.push_back(now - last);
+        }
+        self.left = self.left.saturating_sub(1);
+    }
+
+    /// Returns the estimate as `~12m remaining (count-based)`,
+    /// or `None` until a file has been finished.
+    pub fn describe(&self) -> Option<String> {
+        let last = self.last?;
+        let average = self.times.iter().sum::<Duration>() / self.times.len().max(1) as u32;
+        if average.is_zero() {
+            return None;
+        }
+        let secs = (average * (self.left + 1))
+            .saturating_sub(last.elapsed())
+            .as_secs();
+        let time = match secs {
+            0..=59 => format!("{secs}s"),
+            60..=3599 => format!("{}m", secs / 60),
+            _ => format!("{}h {}m", secs / 3600, secs / 60 % 60),
+        };
+        Some(format!("~{time} remaining (count-based)"))
+    }
+}
@@ -0,0 +1,89 @@
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use curl::easy::{Easy, InfoType};
+
+#[derive(Clone, Copy)]
+enum State {
+    Idle,
+    /// A request was sent at the instant and nothing has been received since.
+    Waiting(Instant),
+    Responded,
+    TimedOut,
+}
+
+/// Aborts requests whose response doesn't start within a timeout after they are sent.
+///
+/// The debug callback sees each request go out and the first bytes of its response
+/// come in, and progress callbacks, which curl calls about once a second even while
+/// nothing arrives, abort the transfer once the response is overdue. A response that
+/// has started is never aborted, however slowly the rest of it arrives.
+#[derive(Clone)]
+pub struct FirstByteTimer {
+    timeout: Duration,
+    state: Arc<Mutex<State>>,
+}
+
+impl FirstByteTimer {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            state: Arc::new(Mutex::new(State::Idle)),
+        }
+    }
+
+    /// Sets the callbacks of the timer on a handle, as the defaults of its transfers.
+    ///
+    /// This turns on verbose mode to receive debug information,
+    /// which is only printed to stderr as libcurl does if `verbose` is set.
+    pub fn install(&self, handle: &mut Easy, verbose: bool) -> Result<(), curl::Error> {
+        handle.verbose(true)?;
+        handle.progress(true)?;
+        let state = self.state.clone();
+        handle.debug_function(move |kind, data| {
+            let mut state = state.lock().unwrap();
+            match kind {
+                // Each request of a redirect chain gets the full timeout.
+                InfoType::HeaderOut if !matches!(*state, State::Waiting(_)) => {
+                    *state = State::Waiting(Instant::now());
+                }
+                InfoType::HeaderIn | InfoType::DataIn => *state = State::Responded,
+                _ => {}
+            }
+            if verbose {
+                let prefix = match kind {
+                    InfoType::Text => "* ",
+                    InfoType::HeaderIn => "< ",
+                    InfoType::HeaderOut => "> ",
+                    _ => return,
+                };
+                let mut stderr = io::stderr().lock();
+                let _ = stderr.write_all(prefix.as_bytes());
+                let _ = stderr.write_all(data);
+            }
+        })?;
+        let timer = self.clone();
+        handle.progress_function(move |_, _, _, _| timer.check())
+    }
+
+    /// Returns `false` to abort the transfer if its response is overdue,
+    /// for progress callbacks set on a transfer in place of the default one.
+    pub fn check(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            State::Waiting(sent) if sent.elapsed() >= self.timeout => {
+                *state = State::TimedOut;
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Returns whether a transfer failed with `e` for being aborted by the timer.
+    pub fn aborted(&self, e: &curl::Error) -> bool {
+        e.is_aborted_by_callback() && matches!(*self.state.lock().unwrap(), State::TimedOut)
+    }
+}
@@ -0,0 +1,50 @@
+use std::{
+    io,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use uuid::Uuid;
+
+use crate::{timestamp, write_atomic};
+
+/// Periodically rewrites a file with the current progress,
+/// so that a watchdog can tell a hung run from a slow one.
+pub struct Heartbeat {
+    path: PathBuf,
+    interval: Duration,
+    run_id: Uuid,
+    last: Option<Instant>,
+}
+
+impl Heartbeat {
+    pub fn new(path: PathBuf, interval: Duration, run_id: Uuid) -> Self {
+        Self {
+            path,
+            interval,
+            run_id,
+            last: None,
+        }
+    }
+
+    /// Updates the file if the interval has passed since the last update.
+    pub fn beat(&mut self, done: u32, current: &str) -> io::Result<()> {
+        if self.last.is_some_and(|last| last.elapsed() < self.interval) {
+            return Ok(());
+        }
+        self.force(done, current)
+    }
+
+    /// Updates the file regardless of the interval.
+    pub fn force(&mut self, done: u32, current: &str) -> io::Result<()> {
+        self.last = Some(Instant::now());
+        write_atomic(
+            &self.path,
+            format!(
+                "run: {}\nupdated: {}\ndone: {done}\ncurrent: {current}\n",
+                self.run_id,
+                timestamp()
+            ),
+        )
+    }
+}
@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+/// Spreads delays randomly around their value, from a seed so that the delays
+/// of a run can be reproduced.
+pub struct Jitter {
+    fraction: f64,
+    state: u64,
+}
+
+impl Jitter {
+    pub fn new(fraction: f64, seed: u64) -> Self {
+        Self {
+            fraction,
+            state: seed,
+        }
+    }
+
+    /// Returns the next number of the SplitMix64 sequence.
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Picks a delay uniformly within the fraction of `delay` on either side of it.
+    pub fn apply(&mut self, delay: Duration) -> Duration {
+        // The top 53 bits make a uniform float in [0, 1).
+        let unit = (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+        delay.mul_f64(1.0 + self.fraction * (2.0 * unit - 1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_delays_repeat_within_range() {
+        let delay = Duration::from_secs(10);
+        let delays = |seed| {
+            let mut jitter = Jitter::new(0.5, seed);
+            (0..100).map(|_| jitter.apply(delay)).collect::<Vec<_>>()
+        };
+        let first = delays(42);
+        assert_eq!(first, delays(42));
+        assert_ne!(first, delays(43));
+        assert!(first
+            .iter()
+            .all(|d| (Duration::from_secs(5)..=Duration::from_secs(15)).contains(d)));
+        assert!(first.iter().any(|d| *d < delay) && first.iter().any(|d| *d > delay));
+    }
+}
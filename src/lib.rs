@@ -0,0 +1,4111 @@
+mod autoindex;
+mod cas;
+mod compare;
+#[cfg(windows)]
+mod ctrl_break;
+mod curl_opt;
+mod decode;
+mod diff;
+mod eta;
+mod first_byte;
+mod heartbeat;
+mod jitter;
+mod logger;
+mod metalink;
+mod mirror_rank;
+mod paginate;
+mod plugin;
+mod query_sign;
+mod redirect;
+mod s3;
+mod schedule;
+#[cfg(unix)]
+mod sparse;
+mod sqlite;
+mod src_api;
+mod torrent;
+
+use std::{
+    borrow::Cow,
+    cell::Cell,
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, IsTerminal, Read, Seek, SeekFrom, Write},
+    iter, mem,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+#[cfg(windows)]
+use std::os::windows::prelude::OpenOptionsExt;
+
+use anyhow::{bail, Context, Result};
+use cas::{Algo, HashCache};
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::{arg, parser::ValueSource, value_parser, ArgAction, ArgGroup, ArgMatches, Command};
+use compare::Comparator;
+use curl::easy::{Easy, List};
+use curl_opt::CurlOpt;
+use decode::{Decoder, Encoding};
+use eta::Eta;
+use first_byte::FirstByteTimer;
+use flate2::read::MultiGzDecoder;
+use heartbeat::Heartbeat;
+use jitter::Jitter;
+use log::LevelFilter;
+use logger::Logged;
+use mirror_rank::MirrorRank;
+use plugin::Plugin;
+use query_sign::QuerySigner;
+use redirect::RedirectGuard;
+use regex::Regex;
+use s3::Signer;
+use schedule::Schedule;
+use sha2::{Digest, Sha256};
+use torrent::{Torrent, Verdict};
+use unicode_normalization::UnicodeNormalization;
+use uuid::Uuid;
+
+#[derive(Clone)]
+enum Source {
+    List(HashMap<String, String>),
+    Template(String),
+}
+
+/// How names are derived from URLs.
+#[derive(Clone, Copy, Default)]
+struct Naming {
+    keep_query: bool,
+    strip_fragment: bool,
+}
+
+fn name_from_url(url: &str, naming: Naming) -> &str {
+    let url = match url.split_once('#') {
+        Some((head, _)) if naming.strip_fragment => head,
+        _ => url,
+    };
+    if !naming.keep_query {
+        let mut name = url;
+        if let Some((_, tail)) = name.rsplit_once('/') {
+            name = tail;
+        }
+        if let Some((head, _)) = name.split_once('?') {
+            name = head;
+        }
+        return name;
+    }
+    // The query may contain slashes of its own, so the path is split before it.
+    let path = url.split_once('?').map_or(url, |(path, _)| path);
+    let start = path.rfind('/').map_or(0, |i| i + 1);
+    &url[start..]
+}
+
+/// Parses a line of a URL list, which is either a URL or a name followed by a URL.
+fn parse_list_line(line: &str, naming: Naming) -> (&str, &str) {
+    let line = split_digest(line).0;
+    // URLs may contain unencoded spaces, so a name is only split off before a full URL.
+    match line.rsplit_once(' ') {
+        Some((name, url)) if url.contains("://") && !name.trim().is_empty() => (name.trim(), url),
+        _ => (name_from_url(line, naming), line),
+    }
+}
+
+/// Splits a trailing ` ALGO:HEX` off a URL list line, giving the digest
+/// the remote file is expected to hash to.
+fn split_digest(line: &str) -> (&str, Option<(Algo, &str)>) {
+    let digest = line.rsplit_once(' ').and_then(|(line, digest)| {
+        let (algo, hex) = digest.split_once(':')?;
+        let algo: Algo = algo.parse().ok()?;
+        let valid = hex.len() == algo.hex_len() && hex.bytes().all(|b| b.is_ascii_hexdigit());
+        valid.then_some((line, (algo, hex)))
+    });
+    match digest {
+        Some((line, digest)) => (line, Some(digest)),
+        None => (line, None),
+    }
+}
+
+/// Loads the expected digests from a URL list or Metalink file, keyed like the source.
+fn load_digests(
+    path: &str,
+    naming: Naming,
+    normalize: bool,
+    ignore_case: bool,
+) -> Result<HashMap<String, (Algo, String)>> {
+    let mut digests = Vec::new();
+    if metalink::is_metalink(Path::new(path)) {
+        for (name, entry) in metalink::load(Path::new(path))? {
+            if let Some(digest) = entry.digest {
+                digests.push((name, digest));
+            }
+        }
+    } else {
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if let (_, Some((algo, hex))) = split_digest(&line) {
+                let (name, _) = parse_list_line(&line, naming);
+                digests.push((name.into(), (algo, hex.to_ascii_lowercase())));
+            }
+        }
+    }
+
+    Ok(digests
+        .into_iter()
+        .map(|(name, digest)| (source_key(name, normalize, ignore_case), digest))
+        .collect())
+}
+
+/// Loads the mirrors of the files of a Metalink after the first, in priority order,
+/// keyed like the source.
+fn load_mirrors(
+    path: &Path,
+    normalize: bool,
+    ignore_case: bool,
+) -> Result<HashMap<String, Vec<String>>> {
+    Ok(metalink::load(path)?
+        .into_iter()
+        .filter(|(_, entry)| entry.urls.len() > 1)
+        .map(|(name, entry)| {
+            let key = source_key(name, normalize, ignore_case);
+            (key, entry.urls.into_iter().skip(1).collect())
+        })
+        .collect())
+}
+
+/// Returns the lookup key of a name taken from a source.
+fn source_key(name: String, normalize: bool, ignore_case: bool) -> String {
+    let name = if normalize {
+        normalize_name(&name, true)
+    } else {
+        name
+    };
+    lookup_key(&name, ignore_case).into_owned()
+}
+
+/// Loads the byte ranges to check of each file from lines of a name
+/// followed by comma-separated inclusive ranges like `0-511,4096-8191`.
+fn load_range_map(
+    path: &Path,
+    normalize: bool,
+    ignore_case: bool,
+) -> Result<HashMap<String, Vec<(u64, u64)>>> {
+    let mut map: HashMap<_, Vec<_>> = HashMap::new();
+    for (i, line) in fs::read_to_string(path)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let invalid = || format!("invalid range map line {}", i + 1);
+        let (name, ranges) = line.rsplit_once(' ').with_context(invalid)?;
+        let name = if normalize {
+            normalize_name(name.trim(), false)
+        } else {
+            name.trim().into()
+        };
+        let regions = map
+            .entry(lookup_key(&name, ignore_case).into_owned())
+            .or_default();
+        for range in ranges.split(',') {
+            let (start, end) = range.split_once('-').with_context(invalid)?;
+            let start: u64 = start.parse().ok().with_context(invalid)?;
+            let end: u64 = end.parse().ok().with_context(invalid)?;
+            if start > end {
+                bail!("{}: range ends before it starts", invalid());
+            }
+            regions.push((start, end));
+        }
+    }
+    Ok(map)
+}
+
+/// Splits a trailing `@OFFSET+LENGTH` off a source URL, giving the byte range
+/// of a remote file that the local file is expected to match.
+fn split_slice(url: &str) -> (&str, Option<(u64, u64)>) {
+    let slice = url.rsplit_once('@').and_then(|(url, slice)| {
+        let (offset, len) = slice.split_once('+')?;
+        let (offset, len) = (offset.parse().ok()?, len.parse().ok()?);
+        (len != 0).then_some((url, (offset, len)))
+    });
+    match slice {
+        Some((url, slice)) => (url, Some(slice)),
+        None => (url, None),
+    }
+}
+
+/// Returns the host in the authority of a URL, without userinfo or port.
+fn url_host(url: &str) -> Option<&str> {
+    let (_, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next().unwrap();
+    let host_port = authority.rsplit_once('@').map_or(authority, |(_, hp)| hp);
+    let host = match host_port.strip_prefix('[') {
+        Some(v6) => &host_port[..v6.find(']')? + 2],
+        None => host_port.split(':').next().unwrap(),
+    };
+    (!host.is_empty()).then_some(host)
+}
+
+impl Source {
+    /// Loads a URL list or Metalink file, or takes `s` as a template if there is no such file.
+    fn load(s: &str, naming: Naming) -> Result<Self> {
+        let path = Path::new(s);
+        if metalink::is_metalink(path) {
+            let files = metalink::load(path)?;
+            Ok(Self::List(
+                files
+                    .into_iter()
+                    .map(|(name, entry)| (name, entry.urls.into_iter().next().unwrap()))
+                    .collect(),
+            ))
+        } else if path.exists() && path.is_file() {
+            let br = BufReader::new(File::open(path)?);
+            let mut map = HashMap::new();
+            for line in br.lines() {
+                let line = line?;
+                let (name, url) = parse_list_line(&line, naming);
+                map.insert(name.into(), url.into());
+            }
+            Ok(Self::List(map))
+        } else {
+            Ok(Self::Template(s.into()))
+        }
+    }
+
+    /// Takes the URL of a file, looked up by the key of its name in a URL list.
+    ///
+    /// Templates are filled with the name itself, which may differ from the key in case.
+    fn provide(&mut self, name: &str, key: &str) -> Option<String> {
+        match self {
+            Self::List(map) => map.remove(key),
+            Self::Template(template) => Some(template.replace("{}", name)),
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            Self::List(_) => "URL list",
+            Self::Template(_) => "template",
+        }
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        match self {
+            Self::List(map) => map.contains_key(name),
+            Self::Template(_) => true,
+        }
+    }
+
+    fn names(&self) -> impl Iterator<Item = &str> {
+        match self {
+            Self::List(map) => Some(map.keys()),
+            Self::Template(_) => None,
+        }
+        .into_iter()
+        .flatten()
+        .map(|name| &name[..])
+    }
+
+    /// Percent-decodes and NFC-normalizes the names derived from a URL list.
+    fn normalize_names(&mut self) {
+        if let Self::List(map) = self {
+            *map = map
+                .drain()
+                .map(|(name, url)| (normalize_name(&name, true), url))
+                .collect();
+        }
+    }
+
+    /// Lowercases the names derived from a URL list for case-insensitive lookups,
+    /// returning the original names by their lowercase ones.
+    fn lowercase_names(&mut self) -> HashMap<String, String> {
+        let mut originals = HashMap::new();
+        if let Self::List(map) = self {
+            *map = map
+                .drain()
+                .map(|(name, url)| {
+                    let lower = name.to_lowercase();
+                    if lower != name {
+                        originals.insert(lower.clone(), name);
+                    }
+                    (lower, url)
+                })
+                .collect();
+        }
+        originals
+    }
+
+    fn remove(&mut self, name: &str) {
+        if let Self::List(map) = self {
+            map.remove(name);
+        }
+    }
+
+    /// Removes the names of a URL list that `f` returns `true` for.
+    fn remove_if(&mut self, mut f: impl FnMut(&str) -> bool) {
+        if let Self::List(map) = self {
+            map.retain(|name, _| !f(name));
+        }
+    }
+
+    fn into_rest(self) -> impl Iterator<Item = (String, String)> {
+        match self {
+            Self::List(map) => Some(map.into_iter()),
+            Self::Template(_) => None,
+        }
+        .into_iter()
+        .flatten()
+    }
+}
+
+/// Classifies a failed request for aggregating errors by their cause.
+fn error_kind(handle: &mut Easy, e: &curl::Error) -> &'static str {
+    if e.is_couldnt_resolve_host() || e.is_couldnt_resolve_proxy() {
+        "dns"
+    } else if e.is_operation_timedout() {
+        // The transfer only begins after connecting, including the TLS handshake.
+        match handle.pretransfer_time() {
+            Ok(t) if !t.is_zero() => "read-timeout",
+            _ => "connect-timeout",
+        }
+    } else if e.is_couldnt_connect() {
+        "connect"
+    } else if e.is_ssl_connect_error()
+        || e.is_peer_failed_verification()
+        || e.is_ssl_certproblem()
+        || e.is_ssl_cipher()
+        || e.is_ssl_cacert_badfile()
+        || e.is_ssl_issuer_error()
+        || e.is_ssl_crl_badfile()
+        || e.is_ssl_shutdown_failed()
+    {
+        "tls"
+    } else if e.is_http_returned_error()
+        || e.is_http2_error()
+        || e.is_http2_stream_error()
+        || e.is_got_nothing()
+        || e.is_bad_content_encoding()
+    {
+        "http"
+    } else if e.is_recv_error() || e.is_send_error() || e.is_partial_file() {
+        "transfer"
+    } else {
+        "other"
+    }
+}
+
+enum Probe {
+    Error(&'static str, curl::Error),
+    Available,
+    NotAvailable,
+    /// Redirected to a login page.
+    AuthRequired,
+    /// Redirected to a host outside the allowlist.
+    RedirectBlocked,
+    Inconclusive,
+}
+
+impl Probe {
+    /// Probes a URL, with `content_type` matching the `Content-Type`
+    /// (or an empty string if there is none) of files that are available.
+    fn run(
+        handle: &mut Easy,
+        url: &str,
+        name: &str,
+        normalize: bool,
+        login: Option<&Regex>,
+        content_type: Option<&Regex>,
+        redirect: Option<&RedirectGuard>,
+    ) -> Self {
+        handle.url(url).unwrap();
+        if let Err(e) = handle.perform() {
+            if redirect.and_then(RedirectGuard::take_blocked).is_some() {
+                return Self::RedirectBlocked;
+            }
+            return Self::Error(error_kind(handle, &e), e);
+        }
+
+        let code = handle.response_code().unwrap();
+        let eff_url = handle.effective_url().unwrap().unwrap();
+        if login.is_some_and(|login| login.is_match(eff_url)) {
+            return Self::AuthRequired;
+        }
+        let has_name =
+            eff_url.contains(name) || normalize && normalize_name(eff_url, true).contains(name);
+        // A server may send an error page for a missing file with a success code.
+        let type_ok = content_type
+            .is_none_or(|pattern| pattern.is_match(handle.content_type().unwrap().unwrap_or("")));
+        if (200..300).contains(&code) && has_name && type_ok {
+            Self::Available
+        } else {
+            Self::NotAvailable
+        }
+    }
+
+    fn agrees_with(&self, other: &Self) -> bool {
+        mem::discriminant(self) == mem::discriminant(other)
+    }
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Normalizes a name to NFC, percent-decoding it first if `decode` is set
+/// and it decodes to valid UTF-8.
+fn normalize_name(name: &str, decode: bool) -> String {
+    let decoded = if decode { percent_decode(name) } else { None };
+    decoded.as_deref().unwrap_or(name).nfc().collect()
+}
+
+/// Returns the key a name is looked up with in the source and the record.
+fn lookup_key(name: &str, ignore_case: bool) -> Cow<'_, str> {
+    if ignore_case {
+        name.to_lowercase().into()
+    } else {
+        name.into()
+    }
+}
+
+fn file_name(path: &Path, normalize: bool) -> Cow<'_, str> {
+    let name = path.file_name().unwrap().to_str().unwrap();
+    if normalize {
+        normalize_name(name, false).into()
+    } else {
+        name.into()
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct Counter {
+    pub good: u32,
+    pub bad: u32,
+    pub na: u32,
+    pub error: u32,
+    /// Local files that are a prefix of a grown remote file, with `--on-growing stale`.
+    pub stale: u32,
+}
+
+impl Counter {
+    pub fn total(&self) -> u32 {
+        self.good + self.bad + self.na + self.error + self.stale
+    }
+
+    /// Adds the counts added to `now` since it was `before`.
+    fn add_since(&mut self, now: &Counter, before: &Counter) {
+        self.good += now.good - before.good;
+        self.bad += now.bad - before.bad;
+        self.na += now.na - before.na;
+        self.error += now.error - before.error;
+        self.stale += now.stale - before.stale;
+    }
+}
+
+impl fmt::Display for Counter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} good, {} bad, {} n/a, {} error",
+            self.good, self.bad, self.na, self.error
+        )?;
+        // Only shown when there are any, as most runs never have them.
+        if self.stale != 0 {
+            write!(f, ", {} stale", self.stale)?;
+        }
+        Ok(())
+    }
+}
+
+/// Results of the downloads from a host.
+#[derive(Default)]
+pub struct HostStats {
+    pub counter: Counter,
+    /// Bytes received from the host.
+    pub bytes: u64,
+    /// Seconds spent downloading from the host.
+    pub secs: f64,
+}
+
+impl HostStats {
+    /// Adds a download started at `start`, with its result counted in `now` since `before`.
+    fn add(&mut self, now: &Counter, before: &Counter, bytes: u64, start: Instant) {
+        self.counter.add_since(now, before);
+        self.bytes += bytes;
+        self.secs += start.elapsed().as_secs_f64();
+    }
+}
+
+fn format_speed(bytes: u64, secs: f64) -> String {
+    let speed = bytes as f64 / secs / 1024.0;
+    if speed >= 1024.0 {
+        format!("{:.1} MB/s", speed / 1024.0)
+    } else {
+        format!("{speed:.1} KB/s")
+    }
+}
+
+/// Appends a tag to every line written through it.
+struct Tagged<W> {
+    inner: W,
+    tag: String,
+}
+
+impl<W: Write> Write for Tagged<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut rest = buf;
+        while let Some(i) = rest.iter().position(|&b| b == b'\n') {
+            self.inner.write_all(&rest[..i])?;
+            self.inner.write_all(self.tag.as_bytes())?;
+            self.inner.write_all(b"\n")?;
+            rest = &rest[i + 1..];
+        }
+        self.inner.write_all(rest)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Stops a run once too many of its results are errors, with `--max-errors`.
+struct ErrorLimit {
+    max: u32,
+    /// Whether only errors in a row count, rather than all errors of the run.
+    consecutive: bool,
+    errors: u32,
+    last: Counter,
+}
+
+impl ErrorLimit {
+    /// Counts the results since the last check, printing a hint and returning
+    /// `true` once the limit is reached.
+    fn check(&mut self, counter: &Counter, out: &mut dyn Write) -> io::Result<bool> {
+        let errors = counter.error - self.last.error;
+        if self.consecutive && counter.total() - self.last.total() > errors {
+            self.errors = 0;
+        }
+        self.errors += errors;
+        self.last = counter.clone();
+        if self.errors < self.max {
+            return Ok(false);
+        }
+        let in_a_row = if self.consecutive { " in a row" } else { "" };
+        writeln!(
+            out,
+            "stopped: {} error(s){in_a_row}, check the credentials and the source URL",
+            self.errors
+        )?;
+        Ok(true)
+    }
+}
+
+/// Counts a status of a compared file.
+fn count_status(counter: &mut Counter, status: &str) {
+    match status {
+        "good" => counter.good += 1,
+        "bad" => counter.bad += 1,
+        "error" => counter.error += 1,
+        _ => counter.stale += 1,
+    }
+}
+
+/// Collects the names and statuses of the record lines written through it.
+struct Outcomes<W> {
+    inner: W,
+    line: Vec<u8>,
+    outcomes: Vec<(String, String)>,
+    /// Times at which the outcomes were recorded.
+    times: Vec<SystemTime>,
+}
+
+impl<W: Write> Write for Outcomes<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        for &b in &buf[..n] {
+            if b != b'\n' {
+                self.line.push(b);
+                continue;
+            }
+            let line = String::from_utf8_lossy(&mem::take(&mut self.line)).into_owned();
+            if let Some((name, status)) = line.split_once(": ") {
+                if status_kind(status) != "checking" {
+                    self.outcomes.push((name.into(), status.into()));
+                    self.times.push(SystemTime::now());
+                }
+            }
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Returns the kind of a recorded status, i.e., `good`, `bad`, `n/a` or `error`.
+fn status_kind(status: &str) -> &str {
+    status.split([' ', ':']).next().unwrap()
+}
+
+/// The kinds of the statuses recorded for names, by their lookup keys.
+#[derive(Default)]
+struct Recorded {
+    kinds: HashMap<String, String>,
+    /// Kinds recorded before the offset saved with `--rec-offset`.
+    before_offset: RecOffset,
+}
+
+impl Recorded {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.kinds
+            .get(key)
+            .or_else(|| self.before_offset.kinds.get(&key_hash(key)))
+            .map(|kind| &kind[..])
+    }
+
+    fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+}
+
+/// Hashes a lookup key, so that the names recorded before an offset are saved compactly.
+fn key_hash(key: &str) -> u64 {
+    xxhash_rust::xxh3::xxh3_64(key.as_bytes())
+}
+
+/// Loads the record, returning the kind of status recorded for each name by its lookup key.
+fn load_rec(
+    file: &mut File,
+    src: &mut Source,
+    counter: &mut Counter,
+    normalize: bool,
+    ignore_case: bool,
+) -> io::Result<HashMap<String, String>> {
+    let mut reader = BufReader::new(file);
+    let mut res = HashMap::new();
+    let mut buf = String::new();
+    while reader.read_line(&mut buf)? != 0 {
+        if buf.ends_with('\n') {
+            buf.pop();
+            if buf.ends_with('\r') {
+                buf.pop();
+            }
+        }
+        // A file still marked as being checked was interrupted, so it is checked again.
+        if let Some((name, status)) = buf
+            .split_once(": ")
+            .filter(|(_, status)| status_kind(status) != "checking")
+        {
+            let name = if normalize {
+                normalize_name(name, false)
+            } else {
+                name.into()
+            };
+            let name = lookup_key(&name, ignore_case).into_owned();
+            src.remove(&name);
+            res.insert(name, status_kind(status).into());
+            match status_kind(status) {
+                "good" => counter.good += 1,
+                "bad" => counter.bad += 1,
+                "n/a" => counter.na += 1,
+                "error" => counter.error += 1,
+                "stale" => counter.stale += 1,
+                _ => (),
+            }
+        }
+        buf.clear();
+    }
+    Ok(res)
+}
+
+/// Returns the path of the file the end of a record is saved to with `--rec-offset`.
+fn offset_path(rec: &str) -> PathBuf {
+    format!("{rec}.offset").into()
+}
+
+/// Returns the path of the file the hashes of the keys recorded before the saved end
+/// of a record are appended to, with the kinds of their statuses.
+fn keys_path(rec: &str) -> PathBuf {
+    format!("{rec}.keys").into()
+}
+
+/// The end of a record saved with `--rec-offset`, and the kinds recorded before it.
+#[derive(Default)]
+struct RecOffset {
+    /// Kinds recorded before the offset by the hashes of their lookup keys.
+    kinds: HashMap<u64, String>,
+    /// Length of the keys file when it was saved, past which an interrupted save
+    /// may have left lines behind.
+    keys_len: u64,
+}
+
+/// Restores the counts saved with the end of a record and seeks the record there,
+/// so that only the lines appended since are loaded.
+///
+/// The hashes of the keys before the offset are still read in full, but they are
+/// much shorter than record lines and are only ever appended to.
+fn load_rec_offset(rec_path: &str, rec: &mut File, counter: &mut Counter) -> Result<RecOffset> {
+    let saved = match fs::read_to_string(offset_path(rec_path)) {
+        Ok(saved) => saved,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(RecOffset::default()),
+        Err(e) => return Err(e).context("failed to read record offset"),
+    };
+    let mut offset = None;
+    let mut keys_len = 0;
+    for line in saved.lines() {
+        let (key, value) = line.split_once(": ").context("invalid record offset")?;
+        let value = value.parse().ok().context("invalid record offset")?;
+        match key {
+            "offset" => offset = Some(value),
+            "keys" => keys_len = value,
+            "good" => counter.good = value as u32,
+            "bad" => counter.bad = value as u32,
+            "n/a" => counter.na = value as u32,
+            "error" => counter.error = value as u32,
+            "stale" => counter.stale = value as u32,
+            _ => bail!("invalid record offset"),
+        }
+    }
+    let offset: u64 = offset.context("invalid record offset")?;
+
+    if offset > rec.metadata()?.len() {
+        bail!("record file is shorter than its saved offset, so it was truncated");
+    }
+    if offset != 0 {
+        let mut last = [0];
+        rec.seek(SeekFrom::Start(offset - 1))?;
+        rec.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            bail!("saved offset is not at the end of a record line");
+        }
+    }
+
+    let mut kinds = HashMap::new();
+    if keys_len != 0 {
+        let keys = File::open(keys_path(rec_path)).context("failed to read record keys")?;
+        if keys.metadata()?.len() < keys_len {
+            bail!("record keys are shorter than their saved length, so they were truncated");
+        }
+        // Later lines are of names recorded again since.
+        for line in BufReader::new(keys.take(keys_len)).lines() {
+            let line = line?;
+            let (hash, kind) = line
+                .split_once(' ')
+                .and_then(|(hash, kind)| Some((u64::from_str_radix(hash, 16).ok()?, kind)))
+                .context("invalid record keys")?;
+            kinds.insert(hash, kind.into());
+        }
+    }
+    Ok(RecOffset { kinds, keys_len })
+}
+
+/// Writes a file through a temporary one renamed over it, so that it is never
+/// seen half-written.
+fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let mut tmp = path.to_path_buf().into_os_string();
+    tmp.push(".tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(tmp, path)
+}
+
+/// Saves the end of a record along with the counts of the lines up to it, appending
+/// the kinds recorded since the last save by the hashes of their keys.
+fn save_rec_offset<'a>(
+    rec: &str,
+    keys_len: u64,
+    counter: &Counter,
+    kinds: impl Iterator<Item = (u64, &'a str)>,
+) -> io::Result<()> {
+    let mut keys = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(keys_path(rec))?;
+    keys.set_len(keys_len)?;
+    keys.seek(SeekFrom::End(0))?;
+    let mut writer = io::BufWriter::new(&mut keys);
+    for (hash, kind) in kinds {
+        writeln!(writer, "{hash:016x} {kind}")?;
+    }
+    writer.flush()?;
+    drop(writer);
+    keys.sync_data()?;
+
+    let offset = fs::metadata(rec)?.len();
+    let keys_len = keys.metadata()?.len();
+    write_atomic(
+        &offset_path(rec),
+        format!(
+            "offset: {offset}\nkeys: {keys_len}\ngood: {}\nbad: {}\nn/a: {}\nerror: {}\nstale: {}\n",
+            counter.good, counter.bad, counter.na, counter.error, counter.stale
+        ),
+    )
+}
+
+fn load_etags(file: &mut File) -> io::Result<HashMap<String, String>> {
+    let mut map = HashMap::new();
+    for line in BufReader::new(file).lines() {
+        if let Some((name, etag)) = line?.split_once(": ") {
+            map.insert(name.into(), etag.into());
+        }
+    }
+    Ok(map)
+}
+
+fn parse_etag(etag: &mut Option<String>, header: &[u8]) {
+    let header = String::from_utf8_lossy(header);
+    if header.starts_with("HTTP/") {
+        *etag = None;
+    } else if let Some((key, value)) = header.split_once(':') {
+        if key.eq_ignore_ascii_case("etag") {
+            *etag = Some(value.trim().to_string());
+        }
+    }
+}
+
+/// Parses the `Content-Length` header of the last response.
+///
+/// curl ignores the header for chunked responses, which is when a proxy may get it wrong.
+fn parse_content_len(len: &mut Option<u64>, header: &[u8]) {
+    let header = String::from_utf8_lossy(header);
+    if header.starts_with("HTTP/") {
+        *len = None;
+    } else if let Some((key, value)) = header.split_once(':') {
+        if key.eq_ignore_ascii_case("content-length") {
+            *len = value.trim().parse().ok();
+        }
+    }
+}
+
+/// Parses the total length out of a `Content-Range` header.
+fn parse_total_len(total: &mut Option<u64>, header: &[u8]) {
+    let header = String::from_utf8_lossy(header);
+    if let Some((key, value)) = header.split_once(':') {
+        if key.eq_ignore_ascii_case("content-range") {
+            *total = value
+                .trim()
+                .rsplit_once('/')
+                .and_then(|(_, t)| t.parse().ok());
+        }
+    }
+}
+
+fn same_content(a: &Path, b: &Path) -> io::Result<bool> {
+    let (mut a, mut b) = (File::open(a)?, File::open(b)?);
+    if a.metadata()?.len() != b.metadata()?.len() {
+        return Ok(false);
+    }
+    let (mut buf_a, mut buf_b) = (vec![0; 16384], vec![0; 16384]);
+    loop {
+        let n = a.read(&mut buf_a)?;
+        if n == 0 {
+            return Ok(true);
+        }
+        b.read_exact(&mut buf_b[..n])?;
+        if buf_a[..n] != buf_b[..n] {
+            return Ok(false);
+        }
+    }
+}
+
+/// Replaces the files of each group with hard links to the first file recorded
+/// with the same status in the run, returning the bytes freed.
+///
+/// Files are compared in full again, as they may have changed since being hashed,
+/// and are left alone across file systems or when already linked.
+fn hardlink_dups<'a>(
+    groups: &'a [Vec<String>],
+    status: impl Fn(&str) -> Option<&'a str>,
+    dry_run: bool,
+    out: &mut impl Write,
+) -> Result<u64> {
+    let mut reclaimed = 0;
+    for group in groups {
+        let mut kept: Vec<(&str, &str)> = vec![];
+        for path in group {
+            // Files without a verdict in the run are never linked.
+            let Some(status) = status(path) else { continue };
+            let Some(&(first, _)) = kept.iter().find(|(_, s)| *s == status) else {
+                kept.push((path, status));
+                continue;
+            };
+            let (from, to) = (Path::new(first), Path::new(path));
+            let (meta_from, meta_to) = (from.metadata()?, to.metadata()?);
+            if !linkable(&meta_from, &meta_to) || !same_content(from, to)? {
+                continue;
+            }
+            if dry_run {
+                writeln!(out, "would link: {path} to {first}")?;
+            } else {
+                let mut tmp = to.as_os_str().to_owned();
+                tmp.push(".howis-link");
+                fs::hard_link(from, &tmp)
+                    .and_then(|_| fs::rename(&tmp, to))
+                    .with_context(|| format!("failed to link {path} to {first}"))?;
+                writeln!(out, "linked: {path} to {first}")?;
+                log::info!("linked {path} to {first}");
+            }
+            reclaimed += meta_to.len();
+        }
+    }
+    Ok(reclaimed)
+}
+
+/// Returns whether two files are on the same file system and not already the same file.
+#[cfg(unix)]
+fn linkable(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() != b.ino()
+}
+
+/// Linking across volumes fails with an error instead.
+#[cfg(not(unix))]
+fn linkable(_: &fs::Metadata, _: &fs::Metadata) -> bool {
+    true
+}
+
+/// Returns the offset of allocated zeros in a file that matched, if `check` is set,
+/// where the remote zeros are expected to be a hole.
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn filled_hole(path: &Path, check: bool) -> io::Result<Option<u64>> {
+    #[cfg(unix)]
+    if check {
+        return sparse::first_filled_hole(&File::open(path)?);
+    }
+    Ok(None)
+}
+
+fn write_filled(out: &mut dyn Write, filled: Option<u64>) -> io::Result<()> {
+    match filled {
+        Some(offset) => writeln!(
+            out,
+            "  sparse: the block at byte {offset} is allocated but holds only zeros"
+        ),
+        None => Ok(()),
+    }
+}
+
+/// Decodes received bytes if there is a decoder, returning the bytes to compare.
+fn feed<'a>(decoder: &mut Option<Decoder>, decoded: &'a mut Vec<u8>, data: &'a [u8]) -> &'a [u8] {
+    match decoder {
+        Some(decoder) => {
+            decoded.clear();
+            decoder.update(data, decoded);
+            decoded
+        }
+        None => data,
+    }
+}
+
+fn write_padding(out: &mut dyn Write, status: &str) -> io::Result<()> {
+    if status == "good: padding" {
+        writeln!(out, "  padding: only zeros follow on the longer side")?;
+    }
+    Ok(())
+}
+
+/// Opens a local file for comparison, decompressing it with `--local-gzip`.
+fn open_local(path: &Path, gzip: bool) -> io::Result<Box<dyn Read>> {
+    let file = File::open(path)?;
+    Ok(if gzip {
+        Box::new(MultiGzDecoder::new(BufReader::new(file)))
+    } else {
+        Box::new(file)
+    })
+}
+
+/// Finishes decoding the reference into the comparators, returning whether it was valid.
+fn finish_decoding<'a>(
+    decoder: &mut Option<Decoder>,
+    cmps: impl IntoIterator<Item = &'a mut Comparator>,
+) -> bool {
+    let Some(decoder) = decoder else {
+        return true;
+    };
+    let mut decoded = Vec::new();
+    let valid = decoder.finish(&mut decoded);
+    for cmp in cmps {
+        cmp.update(&decoded);
+    }
+    valid
+}
+
+/// Options that decide the status of a file compared in full with its reference.
+struct Judge<'a> {
+    decode: Option<Encoding>,
+    check_sparse: bool,
+    ignore_trailing_zeros: bool,
+    on_growing: &'a str,
+}
+
+/// The status of a compared file, along with what `--explain` tells about it.
+struct Judged<'a> {
+    status: &'a str,
+    first_diff: Option<u64>,
+    ended_early: bool,
+    filled: Option<u64>,
+}
+
+impl<'a> Judge<'a> {
+    /// Finishes the comparison of the file at `path`, whose reference may have failed to decode.
+    fn judge(&self, cmp: &mut Comparator, path: &Path, valid: bool) -> io::Result<Judged<'a>> {
+        let first_diff = cmp.finish();
+        let filled = match first_diff {
+            None => filled_hole(path, self.check_sparse)?,
+            Some(_) => None,
+        };
+        let status = match first_diff {
+            _ if !valid => self.decode.unwrap().invalid_status(),
+            None if filled.is_some() => "bad: not sparse",
+            None => "good",
+            Some(_) if self.ignore_trailing_zeros && cmp.only_padding() => "good: padding",
+            Some(_) if cmp.local_is_prefix() => self.on_growing,
+            Some(_) => "bad",
+        };
+        Ok(Judged {
+            status,
+            first_diff,
+            ended_early: cmp.ended_early(),
+            filled,
+        })
+    }
+}
+
+impl Judged<'_> {
+    /// Explains the status for `--explain`.
+    fn explain(&self, out: &mut dyn Write) -> io::Result<()> {
+        write_verdict(out, self.first_diff, self.ended_early)?;
+        write_filled(out, self.filled)?;
+        write_padding(out, self.status)
+    }
+}
+
+/// Counts the status of a file and records it.
+fn record(counter: &mut Counter, rec: &mut dyn Write, name: &str, status: &str) -> io::Result<()> {
+    count_status(counter, status_kind(status));
+    writeln!(rec, "{name}: {status}")
+}
+
+/// Explains the result of a comparison for `--explain`.
+fn write_verdict(
+    out: &mut dyn Write,
+    first_diff: Option<u64>,
+    ended_early: bool,
+) -> io::Result<()> {
+    match first_diff {
+        None => writeln!(out, "  verdict: every byte matches and the lengths agree"),
+        Some(offset) if ended_early => writeln!(
+            out,
+            "  verdict: contents agree up to byte {offset}, where the shorter side ends"
+        ),
+        Some(offset) => writeln!(out, "  verdict: contents first differ at byte {offset}"),
+    }
+}
+
+fn existing_file(s: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(s);
+    if path.is_file() {
+        Ok(path)
+    } else {
+        Err("file not found".into())
+    }
+}
+
+fn print_coverage<'a>(
+    src: &Source,
+    paths: impl Iterator<Item = &'a String>,
+    normalize: bool,
+    originals: Option<&HashMap<String, String>>,
+) {
+    let ignore_case = originals.is_some();
+    let names: HashSet<_> = paths
+        .map(Path::new)
+        .filter(|path| path.is_file())
+        .map(|path| lookup_key(&file_name(path, normalize), ignore_case).into_owned())
+        .collect();
+    let (covered, mut uncovered): (Vec<&str>, Vec<&str>) = names
+        .iter()
+        .map(|name| &name[..])
+        .partition(|name| src.contains(name));
+    let mut unused: Vec<_> = src
+        .names()
+        .filter(|name| !names.contains(*name))
+        .map(|name| originals.and_then(|o| o.get(name)).map_or(name, |o| &o[..]))
+        .collect();
+    uncovered.sort_unstable();
+    unused.sort_unstable();
+
+    println!(
+        "coverage: {} with source, {} without source, {} source(s) without file",
+        covered.len(),
+        uncovered.len(),
+        unused.len()
+    );
+    for name in uncovered {
+        println!("{name}: missing source");
+    }
+    for name in unused {
+        println!("{name}: missing file");
+    }
+}
+
+/// Prints the entries of a source along with problems found in the URL list file
+/// it was loaded from, if any, returning the number of errors.
+fn print_parse(src: &Source, list: Option<&Path>, naming: Naming) -> io::Result<u32> {
+    let (mut warnings, mut errors) = (0, 0);
+    if let Some(list) = list {
+        let mut seen = HashMap::<String, usize>::new();
+        for (i, line) in BufReader::new(File::open(list)?).lines().enumerate() {
+            let (url, n) = (line?, i + 1);
+            if url.trim().is_empty() {
+                println!("warning: line {n}: blank line");
+                warnings += 1;
+                continue;
+            }
+            if !url.contains("://") {
+                println!("error: line {n}: `{url}` is not a URL");
+                errors += 1;
+            }
+            let (name, _) = parse_list_line(&url, naming);
+            if name.is_empty() {
+                println!("error: line {n}: no name in `{url}`");
+                errors += 1;
+            } else if let Some(first) = seen.insert(name.into(), n) {
+                println!("warning: line {n}: `{name}` is also on line {first}, which is ignored");
+                warnings += 1;
+            }
+        }
+    }
+
+    let entries = match src {
+        Source::List(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_unstable();
+            for (name, url) in &entries {
+                println!("{name} <- {url}");
+            }
+            entries.len()
+        }
+        Source::Template(template) => {
+            println!("template: {template}");
+            0
+        }
+    };
+    println!("parsed: {entries} entries, {warnings} warning(s), {errors} error(s)");
+    Ok(errors)
+}
+
+/// Parses a number of bytes with an optional `K`, `M` or `G` suffix.
+fn parse_size(s: &str) -> Option<u64> {
+    let (num, mult) = match s.as_bytes().last()? {
+        b'K' | b'k' => (&s[..s.len() - 1], 1 << 10),
+        b'M' | b'm' => (&s[..s.len() - 1], 1 << 20),
+        b'G' | b'g' => (&s[..s.len() - 1], 1 << 30),
+        _ => (s, 1),
+    };
+    let size: f64 = num.parse().ok()?;
+    (size >= 0.0).then_some((size * mult as f64) as u64)
+}
+
+/// Formats a time in UTC as `YYYY-MM-DD HH:MM:SS`.
+fn utc_datetime(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+/// Returns the current UTC time in RFC 3339 format.
+fn timestamp() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Number of bytes at the start of a file fetched to look for an error page.
+const SOFT_404_LEN: usize = 512;
+
+/// Returns whether the start of a file matches a pattern of error pages,
+/// fetching only that much of it.
+fn is_soft_404(
+    handle: &mut Easy,
+    url: &str,
+    origin: Option<&str>,
+    pattern: &Regex,
+) -> Result<bool, curl::Error> {
+    let mut headers = base_headers(origin);
+    headers
+        .append(&format!("Range: bytes=0-{}", SOFT_404_LEN - 1))
+        .unwrap();
+    handle.http_headers(headers).unwrap();
+    handle.nobody(false).unwrap();
+    handle.url(url).unwrap();
+    let mut body = Vec::with_capacity(SOFT_404_LEN);
+    let mut transfer = handle.transfer();
+    transfer
+        .write_function(|data| {
+            let n = data.len().min(SOFT_404_LEN - body.len());
+            body.extend_from_slice(&data[..n]);
+            // Servers ignoring the range are cut off once enough has arrived.
+            Ok(if body.len() < SOFT_404_LEN {
+                data.len()
+            } else {
+                0
+            })
+        })
+        .unwrap();
+    let res = transfer.perform();
+    drop(transfer);
+    handle.nobody(true).unwrap();
+    handle.http_headers(base_headers(origin)).unwrap();
+    match res {
+        Err(e) if !e.is_write_error() => Err(e),
+        _ => Ok(pattern.is_match(&String::from_utf8_lossy(&body))),
+    }
+}
+
+/// Returns whether a caching proxy has a file, asking it with a HEAD request.
+///
+/// Only a 404 or a failed request counts as a miss, so that other errors
+/// from the cache are recorded rather than hidden by the source.
+fn cache_has(handle: &mut Easy, url: &str, origin: Option<&str>) -> bool {
+    handle.nobody(true).unwrap();
+    handle.http_headers(base_headers(origin)).unwrap();
+    handle.url(url).unwrap();
+    let res = handle.perform();
+    handle.nobody(false).unwrap();
+    res.is_ok() && handle.response_code().unwrap() != 404
+}
+
+/// Returns the headers sent with every request.
+fn base_headers(origin: Option<&str>) -> List {
+    let mut headers = List::new();
+    if let Some(origin) = origin {
+        headers.append(&format!("Origin: {origin}")).unwrap();
+    }
+    headers
+}
+
+/// Returns the stats of the host of the effective URL.
+fn host_stats<'a>(
+    hosts: &'a mut HashMap<String, HostStats>,
+    handle: &mut Easy,
+) -> &'a mut HostStats {
+    let url = handle.effective_url().unwrap().unwrap_or("");
+    let host = url_host(url).unwrap_or("(no host)");
+    hosts.entry(host.into()).or_default()
+}
+
+fn print_hosts<'a>(src: &Source, paths: impl Iterator<Item = &'a String>) {
+    let mut hosts = HashMap::<_, u32>::new();
+    match src {
+        Source::List(map) => {
+            for url in map.values() {
+                *hosts.entry(url_host(url)).or_default() += 1;
+            }
+        }
+        Source::Template(template) => {
+            let files = paths.filter(|path| Path::new(path).is_file()).count();
+            hosts.insert(url_host(template), files as u32);
+        }
+    }
+
+    let mut hosts: Vec<_> = hosts.into_iter().collect();
+    hosts.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    for (host, count) in hosts {
+        println!("{}: {count}", host.unwrap_or("(no host)"));
+    }
+}
+
+/// Prints the results so far and flushes the record if a checkpoint was requested.
+fn checkpoint(
+    requested: &AtomicBool,
+    counter: &Counter,
+    out: &mut dyn Write,
+    rec: &mut dyn Write,
+) -> io::Result<()> {
+    if requested.swap(false, Ordering::Relaxed) {
+        writeln!(out, "checkpoint: {counter}")?;
+        rec.flush()?;
+    }
+    Ok(())
+}
+
+/// Options of a run, resolved from the command line.
+pub struct Config {
+    files: Vec<String>,
+    src: Option<String>,
+    src_api: Option<String>,
+    src_index: Option<String>,
+    src_api_path: String,
+    src_api_cache: String,
+    rec: String,
+    rec_offset: bool,
+    /// Whether results go to a terminal, on which the progress of downloads is drawn.
+    tty: bool,
+    log_file: Option<PathBuf>,
+    log_level: LevelFilter,
+    log_rotate: bool,
+    naming: Naming,
+    tag_run: bool,
+    user: Option<String>,
+    pass: Option<String>,
+    prewarm_url: Option<String>,
+    referer: Option<String>,
+    auto_referer: bool,
+    origin: Option<String>,
+    cert: Option<PathBuf>,
+    key: Option<PathBuf>,
+    key_password: Option<String>,
+    s3: bool,
+    sign_hmac: Option<(String, String)>,
+    cas: bool,
+    hash: Option<Algo>,
+    torrent: Option<PathBuf>,
+    partial_credit: bool,
+    accept_threshold: Option<f64>,
+    hash_verify_remote_only: bool,
+    hash_cache: Option<PathBuf>,
+    use_etags: Option<String>,
+    fail_on_missing_source: bool,
+    mark_checking: bool,
+    empty_is_error: bool,
+    dedup: bool,
+    normalize_names: bool,
+    ignore_name_case: bool,
+    report_dups: bool,
+    group_results: bool,
+    verify_dir_structure: bool,
+    hardlink_duplicates: bool,
+    report_by_host: bool,
+    report_coverage_percent: bool,
+    force_ranged: Option<u64>,
+    remote_dir: Option<PathBuf>,
+    cache_url: Option<String>,
+    redirect_allow_hosts: Vec<String>,
+    other_dir: Option<PathBuf>,
+    local_gzip: bool,
+    /// Status of local files that are a prefix of the remote file: `good`, `bad` or `stale`.
+    on_growing: String,
+    retry_on_bad: u32,
+    /// Fraction of the delay before a retry to randomize it by, on either side.
+    retry_jitter: Option<f64>,
+    seed: Option<u64>,
+    mirror_rank: Option<PathBuf>,
+    reset_mirror_stats: bool,
+    check_sparse: bool,
+    strict_length: bool,
+    first_byte_timeout: Option<Duration>,
+    ignore_trailing_zeros: bool,
+    decode: Option<Encoding>,
+    compare_plugin: Option<PathBuf>,
+    max_bytes: Option<u64>,
+    max_errors: Option<u32>,
+    max_errors_consecutive: bool,
+    explain: bool,
+    login_redirect_pattern: Option<Regex>,
+    pause_before_availability: bool,
+    expect_content_type: Option<Regex>,
+    soft_404_pattern: Option<Regex>,
+    expect_absent: Option<PathBuf>,
+    range_map: Option<PathBuf>,
+    /// First page number of paginated resources, with `--paginate`.
+    paginate: Option<u64>,
+    expect_count: Option<u32>,
+    availability_confirm: u32,
+    delay: Duration,
+    prune_na: Option<PathBuf>,
+    dry_run: bool,
+    checkpoint_on_signal: bool,
+    heartbeat: Option<PathBuf>,
+    heartbeat_interval: Duration,
+    summary_json: Option<PathBuf>,
+    metrics_file: Option<PathBuf>,
+    sqlite: Option<PathBuf>,
+    progress_interval: Duration,
+    bandwidth_schedule: Option<Schedule>,
+    curl_opts: Vec<CurlOpt>,
+}
+
+impl Config {
+    /// Starts building a configuration, with every option at its default.
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder {
+            args: vec!["howis".into()],
+            files: Vec::new(),
+        }
+    }
+
+    pub fn from_matches(matches: &ArgMatches) -> Self {
+        let string = |id| matches.get_one::<String>(id).cloned();
+        let path = |id| matches.get_one::<PathBuf>(id).cloned();
+        let flag = |id| matches.get_flag(id);
+        let secs = |id| Duration::from_secs_f64(*matches.get_one::<f64>(id).unwrap());
+        Self {
+            files: matches
+                .get_many("FILE")
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect(),
+            src: string("src"),
+            src_api: string("src-api"),
+            src_index: string("src-index"),
+            src_api_path: string("src-api-path").unwrap(),
+            src_api_cache: string("src-api-cache").unwrap(),
+            rec: string("rec").unwrap(),
+            rec_offset: flag("rec-offset"),
+            // Results go to stderr when the record is streamed to stdout.
+            tty: if matches.get_one::<String>("rec").unwrap() == "-" {
+                io::stderr().is_terminal()
+            } else {
+                io::stdout().is_terminal()
+            },
+            log_file: path("log-file"),
+            log_level: *matches.get_one("log-level").unwrap(),
+            log_rotate: flag("log-rotate"),
+            naming: Naming {
+                keep_query: flag("keep-query"),
+                strip_fragment: flag("strip-fragment"),
+            },
+            tag_run: flag("tag-run"),
+            user: string("user"),
+            pass: string("pass"),
+            prewarm_url: string("prewarm-url"),
+            referer: string("referer"),
+            auto_referer: flag("auto-referer"),
+            origin: string("origin"),
+            cert: path("cert"),
+            key: path("key"),
+            key_password: string("key-password"),
+            s3: flag("s3"),
+            sign_hmac: matches.get_many::<String>("sign-hmac").map(|mut values| {
+                let secret = values.next().unwrap().clone();
+                (secret, values.next().unwrap().clone())
+            }),
+            cas: flag("cas"),
+            hash: matches.get_one("hash").copied(),
+            torrent: path("torrent"),
+            partial_credit: flag("partial-credit"),
+            accept_threshold: matches.get_one("accept-threshold").copied(),
+            hash_verify_remote_only: flag("hash-verify-remote-only"),
+            hash_cache: path("hash-cache"),
+            use_etags: string("use-etags"),
+            fail_on_missing_source: flag("fail-on-missing-source"),
+            mark_checking: flag("mark-checking"),
+            empty_is_error: flag("empty-is-error"),
+            dedup: flag("dedup"),
+            normalize_names: flag("normalize-names"),
+            ignore_name_case: flag("ignore-name-case"),
+            report_dups: flag("report-dups"),
+            group_results: flag("group-results"),
+            verify_dir_structure: flag("verify-dir-structure"),
+            hardlink_duplicates: flag("hardlink-duplicates"),
+            report_by_host: flag("report-by-host"),
+            report_coverage_percent: flag("report-coverage-percent"),
+            force_ranged: matches.get_one("force-ranged").copied(),
+            remote_dir: path("remote-dir"),
+            cache_url: string("cache-url"),
+            redirect_allow_hosts: matches
+                .get_many("redirect-allow-host")
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect(),
+            other_dir: path("other-dir"),
+            local_gzip: flag("local-gzip"),
+            on_growing: string("on-growing").unwrap(),
+            retry_on_bad: *matches.get_one("retry-on-bad").unwrap(),
+            retry_jitter: matches.get_one("retry-jitter").copied(),
+            seed: matches.get_one("seed").copied(),
+            mirror_rank: path("mirror-rank"),
+            reset_mirror_stats: flag("reset-mirror-stats"),
+            check_sparse: flag("check-sparse"),
+            strict_length: flag("strict-length"),
+            first_byte_timeout: matches
+                .get_one("first-byte-timeout")
+                .map(|&secs| Duration::from_secs(secs)),
+            ignore_trailing_zeros: flag("ignore-trailing-zeros"),
+            decode: matches.get_one("decode").copied(),
+            compare_plugin: path("compare-plugin"),
+            max_bytes: matches.get_one("max-bytes").copied(),
+            max_errors: matches.get_one("max-errors").copied(),
+            max_errors_consecutive: flag("max-errors-consecutive"),
+            explain: flag("explain"),
+            login_redirect_pattern: matches.get_one("login-redirect-pattern").cloned(),
+            pause_before_availability: flag("pause-before-availability"),
+            expect_content_type: matches.get_one("expect-content-type").cloned(),
+            soft_404_pattern: matches.get_one("soft-404-pattern").cloned(),
+            expect_absent: path("expect-absent"),
+            range_map: path("range-map"),
+            paginate: flag("paginate").then(|| *matches.get_one("first-page").unwrap()),
+            expect_count: matches.get_one("expect-count").copied(),
+            availability_confirm: *matches.get_one("availability-confirm").unwrap(),
+            delay: secs("delay"),
+            prune_na: path("prune-na"),
+            dry_run: flag("dry-run"),
+            checkpoint_on_signal: flag("checkpoint-on-signal"),
+            heartbeat: path("heartbeat"),
+            heartbeat_interval: secs("heartbeat-interval"),
+            summary_json: path("summary-json"),
+            metrics_file: path("metrics-file"),
+            sqlite: path("sqlite"),
+            progress_interval: Duration::from_millis(
+                *matches.get_one("progress-interval").unwrap(),
+            ),
+            bandwidth_schedule: matches.get_one("bandwidth-schedule").cloned(),
+            curl_opts: matches
+                .get_many("curl-opt")
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// Builds a [`Config`] from options named as on the command line, which are
+/// validated and given their defaults the same way.
+pub struct ConfigBuilder {
+    args: Vec<OsString>,
+    files: Vec<OsString>,
+}
+
+impl ConfigBuilder {
+    /// Adds a file to check.
+    pub fn file(mut self, path: impl Into<OsString>) -> Self {
+        self.files.push(path.into());
+        self
+    }
+
+    /// Sets the source URL list file or template string.
+    pub fn src(self, src: impl Into<OsString>) -> Self {
+        self.option("src", src)
+    }
+
+    /// Sets the record file.
+    pub fn rec(self, rec: impl Into<OsString>) -> Self {
+        self.option("rec", rec)
+    }
+
+    /// Sets an option taking a value, such as `"retry-on-bad"`, as if given on the
+    /// command line.
+    pub fn option(mut self, name: &str, value: impl Into<OsString>) -> Self {
+        self.args.push(format!("--{name}").into());
+        self.args.push(value.into());
+        self
+    }
+
+    /// Sets a flag, such as `"explain"`.
+    pub fn flag(mut self, name: &str) -> Self {
+        self.args.push(format!("--{name}").into());
+        self
+    }
+
+    pub fn build(mut self) -> Result<Config, clap::Error> {
+        // Files are never taken for options or the subcommand, whatever their names.
+        self.args.push("--".into());
+        self.args.append(&mut self.files);
+        let matches = command().try_get_matches_from(self.args)?;
+        Ok(Config::from_matches(&matches))
+    }
+}
+
+/// The results of a run.
+pub struct RunReport {
+    run_id: Uuid,
+    /// Counts including the results loaded from the record.
+    pub counter: Counter,
+    /// Names and statuses recorded in the run, in order.
+    pub outcomes: Vec<(String, String)>,
+    /// Times at which the outcomes were recorded.
+    recorded_at: Vec<SystemTime>,
+    /// Bytes received for and time taken by each file downloaded, with `--sqlite`.
+    transfers: Option<HashMap<String, (u64, Duration)>>,
+    /// Bytes received in the run.
+    bytes: u64,
+    elapsed: Duration,
+    /// Results of the downloads by host, with `--report-by-host`.
+    hosts: Option<HashMap<String, HostStats>>,
+    /// Sorted groups of files with identical content, with `--report-dups`.
+    dups: Option<Vec<Vec<String>>>,
+    /// Bytes freed by `--hardlink-duplicates`, or that would be with `--dry-run`.
+    reclaimed: Option<u64>,
+    /// Whether the run was stopped by `--max-errors`.
+    error_limit_reached: bool,
+    /// Numbers of names in the URL list recorded as good and in total,
+    /// with `--report-coverage-percent`.
+    coverage: Option<(usize, usize)>,
+}
+
+impl RunReport {
+    pub fn run_id(&self) -> Uuid {
+        self.run_id
+    }
+
+    /// Times at which the outcomes were recorded.
+    pub fn recorded_at(&self) -> &[SystemTime] {
+        &self.recorded_at
+    }
+
+    /// Bytes received for and time taken by each file downloaded, with `--sqlite`.
+    pub fn transfers(&self) -> Option<&HashMap<String, (u64, Duration)>> {
+        self.transfers.as_ref()
+    }
+
+    /// Bytes received in the run.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Results of the downloads by host, with `--report-by-host`.
+    pub fn hosts(&self) -> Option<&HashMap<String, HostStats>> {
+        self.hosts.as_ref()
+    }
+
+    /// Sorted groups of files with identical content, with `--report-dups`.
+    pub fn dups(&self) -> Option<&[Vec<String>]> {
+        self.dups.as_deref()
+    }
+
+    /// Bytes freed by `--hardlink-duplicates`, or that would be with `--dry-run`.
+    pub fn reclaimed(&self) -> Option<u64> {
+        self.reclaimed
+    }
+
+    /// Whether the run was stopped by `--max-errors`.
+    pub fn error_limit_reached(&self) -> bool {
+        self.error_limit_reached
+    }
+
+    /// Numbers of names in the URL list recorded as good and in total,
+    /// with `--report-coverage-percent`.
+    pub fn coverage(&self) -> Option<(usize, usize)> {
+        self.coverage
+    }
+}
+
+/// Loads the source, fetching it from the source API if there is no URL list or template.
+fn load_source(config: &Config) -> Result<Source> {
+    let naming = config.naming;
+    let mut src = match (&config.src, &config.src_api) {
+        // Standard input is read as the content of the only file.
+        (Some(src), _) if src == "-" => Source::Template(src.clone()),
+        (Some(src), _) => Source::load(src, naming).context("failed to read URL list")?,
+        (None, None) => match &config.src_index {
+            Some(url) => {
+                let body = src_api::fetch(url, config.user.as_ref(), config.pass.as_ref())
+                    .context("failed to fetch source index")?;
+                Source::List(autoindex::parse(&body, url, naming))
+            }
+            None => Source::List(HashMap::new()),
+        },
+        (None, Some(url)) => {
+            let cache = Path::new(&config.src_api_cache);
+            let path = &config.src_api_path;
+            let cached = cache.is_file();
+            let body = if cached {
+                src_api::load_cache(cache, url, path).context("failed to read source API cache")?
+            } else {
+                src_api::fetch(url, config.user.as_ref(), config.pass.as_ref())
+                    .context("failed to fetch source API")?
+            };
+            let map = src_api::parse(&body, path, naming)
+                .context("failed to parse source API response")?;
+            if !cached {
+                src_api::save_cache(cache, url, path, &body)
+                    .context("failed to write source API cache")?;
+            }
+            Source::List(map)
+        }
+    };
+    if config.normalize_names {
+        src.normalize_names();
+    }
+    Ok(src)
+}
+
+/// Options that change the results of a run, which a session must be resumed with.
+const SESSION_OPTIONS: &[&str] = &[
+    "src",
+    "src-api",
+    "src-api-path",
+    "src-index",
+    "rec",
+    "rec-offset",
+    "src-api-cache",
+    "cache-url",
+    "prewarm-url",
+    "fail-on-missing-source",
+    "keep-query",
+    "strip-fragment",
+    "s3",
+    "sign-hmac",
+    "cas",
+    "hash",
+    "hash-verify-remote-only",
+    "use-etags",
+    "dedup",
+    "torrent",
+    "partial-credit",
+    "accept-threshold",
+    "empty-is-error",
+    "normalize-names",
+    "ignore-name-case",
+    "force-ranged",
+    "range-map",
+    "paginate",
+    "first-page",
+    "remote-dir",
+    "other-dir",
+    "redirect-allow-host",
+    "local-gzip",
+    "on-growing",
+    "strict-length",
+    "ignore-trailing-zeros",
+    "decode",
+    "check-sparse",
+    "retry-on-bad",
+    "compare-plugin",
+    "login-redirect-pattern",
+    "expect-content-type",
+    "soft-404-pattern",
+    "expect-absent",
+    "availability-confirm",
+];
+
+/// Returns the session options given on the command line with their values.
+///
+/// Secrets are saved as their SHA-256 digests.
+fn session_options(matches: &ArgMatches) -> Vec<(&'static str, String)> {
+    SESSION_OPTIONS
+        .iter()
+        .filter(|id| matches.value_source(id) == Some(ValueSource::CommandLine))
+        .map(|&id| {
+            let mut values: Vec<_> = matches
+                .get_raw(id)
+                .unwrap()
+                .map(|value| value.to_string_lossy().into_owned())
+                .collect();
+            if id == "sign-hmac" {
+                values[0] = format!("sha256:{}", s3::hex(&Sha256::digest(&values[0])));
+            }
+            (id, values.join(" "))
+        })
+        .collect()
+}
+
+/// The progress of a session, saved after each of its runs.
+#[derive(Default)]
+struct SessionProgress {
+    runs: u32,
+    /// Length of the record at the end of the last run, below which it must not shrink.
+    rec_len: u64,
+    /// Counts of the record at the end of the last run.
+    counter: Counter,
+    /// Name of the file recorded last.
+    last: Option<String>,
+}
+
+/// Starts a session by saving its options, or checks that it is resumed with the same ones
+/// and the record it left behind, returning its progress.
+fn open_session(path: &Path, options: &[(&str, String)], rec: &str) -> Result<SessionProgress> {
+    let saved = match fs::read_to_string(path) {
+        Ok(saved) => saved,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let progress = SessionProgress::default();
+            save_session(path, options, &progress).context("failed to save session")?;
+            return Ok(progress);
+        }
+        Err(e) => return Err(e).context("failed to read session"),
+    };
+    let mut saved_options = HashMap::new();
+    let mut progress = SessionProgress::default();
+    for line in saved.lines() {
+        let (key, value) = line.split_once(": ").unwrap_or((line, ""));
+        if let Some(id) = key.strip_prefix("--") {
+            saved_options.insert(id, value);
+            continue;
+        }
+        let count = || value.parse().ok().context("invalid session");
+        match key {
+            "runs" => progress.runs = count()?,
+            "record" => progress.rec_len = value.parse().ok().context("invalid session")?,
+            "good" => progress.counter.good = count()?,
+            "bad" => progress.counter.bad = count()?,
+            "n/a" => progress.counter.na = count()?,
+            "error" => progress.counter.error = count()?,
+            "stale" => progress.counter.stale = count()?,
+            "last" => progress.last = Some(value.into()),
+            _ => bail!("invalid session"),
+        }
+    }
+    let given: HashMap<_, _> = options
+        .iter()
+        .map(|(id, value)| (*id, &value[..]))
+        .collect();
+    let changed: Vec<_> = SESSION_OPTIONS
+        .iter()
+        .filter(|id| saved_options.get(*id) != given.get(*id))
+        .map(|id| format!("--{id}"))
+        .collect();
+    if !changed.is_empty() {
+        bail!(
+            "{} changed since the session was started; delete {} to start over",
+            changed.join(", "),
+            path.display()
+        );
+    }
+    let rec_len = fs::metadata(rec).map_or(0, |meta| meta.len());
+    if rec_len < progress.rec_len {
+        bail!(
+            "the record is shorter than the session left it, so it was rewritten; delete {} to start over",
+            path.display()
+        );
+    }
+    Ok(progress)
+}
+
+/// Saves the options of a session along with its progress.
+fn save_session(
+    path: &Path,
+    options: &[(&str, String)],
+    progress: &SessionProgress,
+) -> io::Result<()> {
+    let mut saved: String = options
+        .iter()
+        .map(|(id, value)| format!("--{id}: {value}\n"))
+        .collect();
+    let counter = &progress.counter;
+    saved += &format!(
+        "runs: {}\nrecord: {}\ngood: {}\nbad: {}\nn/a: {}\nerror: {}\nstale: {}\n",
+        progress.runs,
+        progress.rec_len,
+        counter.good,
+        counter.bad,
+        counter.na,
+        counter.error,
+        counter.stale
+    );
+    if let Some(last) = &progress.last {
+        saved += &format!("last: {last}\n");
+    }
+    write_atomic(path, saved)
+}
+
+/// Writes the final counts of a run as JSON, through a temporary file
+/// so that readers never see a partial summary.
+fn write_summary(path: &Path, report: &RunReport) -> io::Result<()> {
+    let counter = &report.counter;
+    let mut summary = serde_json::json!({
+        "run": report.run_id.to_string(),
+        "good": counter.good,
+        "bad": counter.bad,
+        "na": counter.na,
+        "error": counter.error,
+        "stale": counter.stale,
+        "recorded": report.outcomes.len(),
+        "bytes": report.bytes,
+        "elapsed": report.elapsed.as_secs_f64(),
+    });
+    if let Some(reclaimed) = report.reclaimed {
+        summary["reclaimed"] = reclaimed.into();
+    }
+    if let Some((good, total)) = report.coverage {
+        summary["coverage"] = serde_json::json!({ "good": good, "total": total });
+    }
+    write_atomic(path, format!("{summary}\n"))
+}
+
+/// Writes the results of a run grouped by status, the most pressing first,
+/// and sorted by name within each group.
+fn write_grouped(out: &mut impl Write, outcomes: &[(String, String)]) -> io::Result<()> {
+    // A name checked twice, e.g. after a failed rename, keeps its last result.
+    let latest: HashMap<&str, &str> = outcomes
+        .iter()
+        .map(|(name, status)| (&name[..], &status[..]))
+        .collect();
+    for kind in ["bad", "error", "n/a", "stale", "good"] {
+        let mut group: Vec<_> = latest
+            .iter()
+            .filter(|(_, status)| status_kind(status) == kind)
+            .collect();
+        if group.is_empty() {
+            continue;
+        }
+        group.sort_unstable();
+        writeln!(out, "{kind}: {} file(s)", group.len())?;
+        for (name, status) in group {
+            writeln!(out, "  {name}: {status}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the results of a run as a diff of the local files against the source,
+/// sorted by name, with `-` for files missing locally and `+` for extra ones.
+fn write_structure(out: &mut impl Write, outcomes: &[(String, String)]) -> io::Result<()> {
+    let mut latest: Vec<_> = outcomes
+        .iter()
+        .map(|(name, status)| (&name[..], &status[..]))
+        .collect::<HashMap<_, _>>()
+        .into_iter()
+        .collect();
+    latest.sort_unstable();
+    // A file is only probed for availability when there is no local copy of it.
+    let missing = latest
+        .iter()
+        .filter(|(_, s)| *s == "error: available")
+        .count();
+    let extra = latest
+        .iter()
+        .filter(|(_, s)| *s == "error: missing source")
+        .count();
+    writeln!(
+        out,
+        "structure: {missing} missing locally, {extra} extra locally, {} checked",
+        latest.len() - missing - extra
+    )?;
+    for (name, status) in latest {
+        match status {
+            "error: available" => writeln!(out, "- {name} (missing-local)")?,
+            "error: missing source" => writeln!(out, "+ {name} (extra-local)")?,
+            _ => writeln!(out, "  {name}: {status}")?,
+        }
+    }
+    Ok(())
+}
+
+/// Appends the outcomes of the run to a SQLite database, along with the size
+/// and speed of the transfer of each file downloaded.
+fn export_sqlite(path: &Path, report: &RunReport) -> rusqlite::Result<()> {
+    let transfers = report.transfers.as_ref();
+    let rows =
+        report
+            .outcomes
+            .iter()
+            .zip(&report.recorded_at)
+            .map(|((name, status), &recorded_at)| {
+                let transfer = transfers.and_then(|t| t.get(name));
+                sqlite::Row {
+                    name,
+                    status,
+                    bytes: transfer.map(|&(bytes, _)| bytes),
+                    speed: transfer.map(|&(bytes, elapsed)| bytes as f64 / elapsed.as_secs_f64()),
+                    recorded_at,
+                }
+            });
+    sqlite::export(path, &report.run_id.to_string(), rows)
+}
+
+/// Writes the final counts in the Prometheus text format, for the textfile collector
+/// of node_exporter, replacing the file at once so that it is never read half-written.
+fn write_metrics(path: &Path, report: &RunReport) -> io::Result<()> {
+    let counter = &report.counter;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let mut metrics = String::new();
+    metrics += "# HELP howis_files Files by status, including those loaded from the record.\n";
+    metrics += "# TYPE howis_files gauge\n";
+    for (status, n) in [
+        ("good", counter.good),
+        ("bad", counter.bad),
+        ("na", counter.na),
+        ("error", counter.error),
+        ("stale", counter.stale),
+    ] {
+        metrics += &format!("howis_files{{status=\"{status}\"}} {n}\n");
+    }
+    for (name, help, value) in [
+        (
+            "howis_recorded",
+            "Results recorded in the last run.",
+            report.outcomes.len() as f64,
+        ),
+        (
+            "howis_received_bytes",
+            "Bytes received in the last run.",
+            report.bytes as f64,
+        ),
+        (
+            "howis_run_duration_seconds",
+            "Duration of the last run.",
+            report.elapsed.as_secs_f64(),
+        ),
+        (
+            "howis_last_run_timestamp_seconds",
+            "Time the last run finished at.",
+            now.as_secs_f64(),
+        ),
+    ] {
+        metrics += &format!("# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}\n");
+    }
+    write_atomic(path, metrics)
+}
+
+/// Builds the command line interface.
+pub fn command() -> Command {
+    Command::new("howis")
+        .version(env!("CARGO_PKG_VERSION"))
+        .arg(arg!([FILE] ... "Files to check integrity of").required_unless_present_any(["parse-only", "hash-verify-remote-only", "list-hosts"]))
+        .arg(
+            arg!(-s --src <SRC> "Source URL list file or template string, or - to read the only file from stdin")
+                .required_unless_present_any(["src-api", "src-index", "cas", "torrent", "remote-dir"]),
+        )
+        .arg(arg!(--"src-api" <URL> "JSON API to fetch the source URL list from").conflicts_with("src"))
+        .arg(
+            arg!(--"src-api-path" <PATH> "Dot-separated path to the list in the JSON response")
+                .default_value(""),
+        )
+        .arg(
+            arg!(--"src-api-cache" <FILE> "Cache of the JSON response to resume with")
+                .default_value("howis.json"),
+        )
+        .arg(
+            arg!(--"src-index" <URL> "Directory listing page to take the source URL list from")
+                .conflicts_with_all(["src", "src-api"]),
+        )
+        .arg(
+            arg!(-r --rec <FILE> "Record file to resume progress from, or - for stdout")
+                .default_value("howis.txt"),
+        )
+        .arg(arg!(--"rec-offset" "Only load the record appended since the end saved in <FILE>.offset"))
+        .arg(
+            arg!(--session <FILE> "Session file to save the options to, refusing to resume with different ones")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(arg!(--"log-file" <FILE> "Log file to append timestamped entries to").value_parser(value_parser!(PathBuf)))
+        .arg(
+            arg!(--"log-level" <LEVEL> "Maximum level of entries to log")
+                .default_value("info")
+                .value_parser(value_parser!(LevelFilter)),
+        )
+        .arg(arg!(--"log-rotate" "Rename an existing log file to <FILE>.1 instead of appending"))
+        .arg(arg!(--"keep-query" "Keep the query string in names derived from URLs"))
+        .arg(arg!(--"strip-fragment" "Strip the fragment from names derived from URLs"))
+        .arg(arg!(--"tag-run" "Tag every record line with the ID of the run"))
+        .arg(arg!(-u --user <USER> "Server username"))
+        .arg(arg!(-p --pass <PASS> "Server password"))
+        .arg(arg!(--"prewarm-url" <URL> "URL to visit first to set up cookies for the session"))
+        .arg(arg!(--referer <URL> "Referer header to send with every request"))
+        .arg(arg!(--"auto-referer" "Send the previous URL as the Referer when following redirects"))
+        .arg(arg!(--origin <ORIGIN> "Origin header to send with every request"))
+        .arg(arg!(--cert <PATH> "Client certificate file").value_parser(existing_file))
+        .arg(arg!(--key <PATH> "Client private key file").value_parser(existing_file))
+        .arg(arg!(--"key-password" <PASS> "Client private key password"))
+        .arg(arg!(--s3 "Sign requests to s3:// sources with AWS credentials"))
+        .arg(
+            arg!(--"sign-hmac" <SECRET> "Sign URLs with an HMAC-SHA256 of their path and query in the query parameter PARAM")
+                .num_args(2)
+                .value_names(["SECRET", "PARAM"])
+                .conflicts_with("s3"),
+        )
+        .arg(arg!(--cas "Check that file contents hash to the digests their names start with"))
+        .arg(
+            arg!(--hash <ALGO> "Hash algorithm of content addresses [default: inferred from length]")
+                .requires("cas")
+                .value_parser(value_parser!(Algo)),
+        )
+        .arg(
+            arg!(--"hash-verify-remote-only" "Check that remote files hash to the digests in the URL list, without local files")
+                .requires("src"),
+        )
+        .arg(
+            arg!(--torrent <FILE> "Torrent file to verify piece hashes from")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"partial-credit" "Record the fraction of failed pieces instead of their indices")
+                .requires("torrent"),
+        )
+        .arg(
+            arg!(--"accept-threshold" <FRACTION> "Fraction of pieces that must match for a file to be good")
+                .value_parser(|s: &str| {
+                    s.parse()
+                        .ok()
+                        .filter(|t| (0.0..=1.0).contains(t))
+                        .ok_or("expected a fraction between 0 and 1")
+                })
+                .requires("partial-credit"),
+        )
+        .arg(
+            arg!(--"hash-cache" <DIR> "Directory to cache digests of unchanged files in")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(arg!(--"use-etags" <FILE> "ETag store to skip unchanged files with"))
+        // Without a URL source, files are only checked offline and none has a source.
+        .arg(arg!(--"fail-on-missing-source" "Abort if any file has no source").requires("url-source"))
+        .group(ArgGroup::new("url-source").args(["src", "src-api", "src-index"]))
+        .arg(arg!(--"mark-checking" "Record each file as being checked before checking it"))
+        .arg(arg!(--"empty-is-error" "Record empty files as errors without comparing"))
+        .arg(arg!(--dedup "Reuse results for files with the same remote content"))
+        .arg(arg!(--"normalize-names" "Percent-decode and NFC-normalize file names"))
+        .arg(arg!(--"ignore-name-case" "Look up names in the source and the record case-insensitively"))
+        .arg(arg!(--"report-dups" "Report files with identical content after the run"))
+        .arg(
+            arg!(--"hardlink-duplicates" "Replace duplicates with hard links to the first file of the same status")
+                .requires("report-dups"),
+        )
+        .arg(arg!(--"group-results" "List the results of the run by status and name after the run"))
+        .arg(arg!(--"verify-dir-structure" "Report the files missing locally and those extra locally after the run"))
+        .arg(arg!(--"report-by-host" "Report the results and speed of downloads by host after the run"))
+        .arg(arg!(--"report-coverage-percent" "Report the share of the URL list recorded as good after the run"))
+        .arg(
+            arg!(--"force-ranged" <BYTES> "Download files as consecutive ranges of this size")
+                .value_parser(value_parser!(u64).range(1..)),
+        )
+        .arg(
+            arg!(--"range-map" <FILE> "List of byte ranges to only check of each file listed")
+                .conflicts_with_all(["local-gzip", "force-ranged"])
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--paginate "Assemble remote files from the pages of source URLs with a {page} placeholder")
+                .conflicts_with_all(["s3", "range-map", "force-ranged", "decode", "dedup"]),
+        )
+        .arg(
+            arg!(--"first-page" <N> "Number of the first page with --paginate")
+                .value_parser(value_parser!(u64))
+                .default_value("1")
+                .requires("paginate"),
+        )
+        .arg(
+            arg!(--"remote-dir" <DIR> "Directory of downloaded remote copies to compare with offline")
+                .conflicts_with_all(["src", "src-api", "src-index"])
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(arg!(--"cache-url" <BASE> "Caching proxy to request files from first, falling through to the source on 404"))
+        .arg(
+            arg!(--"redirect-allow-host" <HOST> "Host that redirects may lead to, blocking redirects to any other host")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            arg!(--"other-dir" <DIR> "Directory with other copies of the files to find the correct one")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(arg!(--"local-gzip" "Decompress files with gzip before comparing"))
+        .arg(
+            arg!(--"on-growing" <STATUS> "Status of files that are a prefix of a longer remote file")
+                .value_parser(["good", "bad", "stale"])
+                .default_value("bad"),
+        )
+        .arg(
+            arg!(--decode <ENCODING> "Encoding to decode remote files from before comparing: base64 or hex")
+                .conflicts_with_all(["force-ranged", "range-map"])
+                .value_parser(value_parser!(Encoding)),
+        )
+        .arg(arg!(--"ignore-trailing-zeros" "Record files as good where one side only goes on with zeros"))
+        .arg(
+            arg!(--"first-byte-timeout" <SECS> "Give up on requests the server sends nothing back to for this long")
+                .value_parser(value_parser!(u64).range(1..)),
+        )
+        .arg(arg!(--"strict-length" "Record an error where Content-Length differs from the bytes received"))
+        .arg(
+            arg!(--"check-sparse" "Treat matching files as bad where their zeros aren't holes")
+                .conflicts_with("local-gzip"),
+        )
+        .arg(
+            arg!(--"retry-on-bad" <N> "Number of times to download a file again after it is bad")
+                .default_value("0")
+                .value_parser(value_parser!(u32)),
+        )
+        .arg(
+            arg!(--"retry-jitter" <FRACTION> "Fraction of --delay to randomize retries by on either side")
+                .value_parser(|s: &str| {
+                    s.parse()
+                        .ok()
+                        .filter(|t| (0.0..=1.0).contains(t))
+                        .ok_or("expected a fraction between 0 and 1")
+                }),
+        )
+        .arg(
+            arg!(--seed <N> "Seed for the random delays of --retry-jitter, to reproduce them")
+                .value_parser(value_parser!(u64))
+                .requires("retry-jitter"),
+        )
+        .arg(
+            arg!(--"mirror-rank" <FILE> "File of past mirror stats to try the best mirror of a file first by")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"reset-mirror-stats" "Forget the mirror stats saved before the run")
+                .requires("mirror-rank"),
+        )
+        .arg(
+            arg!(--"compare-plugin" <LIB> "Dynamic library deciding the equality of contents")
+                .value_parser(existing_file),
+        )
+        .arg(
+            arg!(--"max-bytes" <SIZE> "Stop starting downloads after this many bytes in total")
+                .value_parser(|s: &str| parse_size(s).ok_or("expected a size such as 500M")),
+        )
+        .arg(
+            arg!(--"max-errors" <N> "Stop the run once this many of its results are errors")
+                .value_parser(value_parser!(u32).range(1..)),
+        )
+        .arg(
+            arg!(--"max-errors-consecutive" "Only count errors in a row towards --max-errors")
+                .requires("max-errors"),
+        )
+        .arg(arg!(--explain "Explain how the result of each file was decided"))
+        .arg(arg!(--"parse-only" "Validate the source and print its entries, then exit"))
+        .arg(arg!(--"list-hosts" "List the hosts of source URLs with counts and exit"))
+        .arg(arg!(--coverage "Report which files have a source and exit"))
+        .arg(
+            arg!(--"login-redirect-pattern" <REGEX> "Treat effective URLs matching this as login redirects")
+                .value_parser(value_parser!(Regex)),
+        )
+        .arg(
+            arg!(--"expect-content-type" <REGEX> "Only treat files with a matching Content-Type as available")
+                .value_parser(value_parser!(Regex)),
+        )
+        .arg(
+            arg!(--"soft-404-pattern" <REGEX> "Treat available files whose first 512 bytes match as unavailable")
+                .value_parser(value_parser!(Regex))
+                .conflicts_with("s3"),
+        )
+        .arg(arg!(--"pause-before-availability" "Wait for confirmation before probing availability"))
+        .arg(
+            arg!(--"expect-absent" <FILE> "List of names expected to be unavailable from the source")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"availability-confirm" <N> "Number of agreeing probes required to record availability")
+                .default_value("1")
+                .value_parser(value_parser!(u32).range(1..)),
+        )
+        .arg(
+            arg!(--delay <SECS> "Delay between repeated availability probes and downloads")
+                .default_value("1")
+                .value_parser(value_parser!(f64)),
+        )
+        .arg(
+            arg!(--"prune-na" <DIR> "Directory to delete files found to be unavailable from")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(arg!(--"dry-run" "Only print the files --prune-na or --hardlink-duplicates would change").requires("mutation"))
+        .group(ArgGroup::new("mutation").args(["prune-na", "hardlink-duplicates"]).multiple(true))
+        .arg(arg!(--"checkpoint-on-signal" "Print the results so far on SIGUSR1, or Ctrl+Break on Windows"))
+        .arg(arg!(--heartbeat <FILE> "File to keep updated with the progress").value_parser(value_parser!(PathBuf)))
+        .arg(
+            arg!(--"summary-json" <FILE> "File to write the final counts to as JSON")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"metrics-file" <FILE> "File to write the final counts to as Prometheus metrics")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--sqlite <PATH> "SQLite database to append the results of every run to")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"expect-count" <N> "Number of files the record should hold after the run")
+                .value_parser(value_parser!(u32)),
+        )
+        .arg(
+            arg!(--"heartbeat-interval" <SECS> "Minimum interval between heartbeat updates")
+                .default_value("30")
+                .value_parser(value_parser!(f64)),
+        )
+        .arg(
+            arg!(--"progress-interval" <MS> "Minimum interval between progress updates")
+                .default_value("200")
+                .value_parser(value_parser!(u64)),
+        )
+        .arg(
+            arg!(--"bandwidth-schedule" <SCHEDULE> "Receive speed limits by time of day, as HH:MM-HH:MM=RATE,...")
+                .value_parser(value_parser!(Schedule)),
+        )
+        .arg(
+            arg!(--"curl-opt" <OPT> "Extra libcurl option as KEY=VALUE")
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(CurlOpt)),
+        )
+        .subcommand(
+            Command::new("diff")
+                .about("Report status changes between two record files")
+                .arg(arg!(<OLD> "Old record file").value_parser(value_parser!(PathBuf)))
+                .arg(arg!(<NEW> "New record file").value_parser(value_parser!(PathBuf)))
+                .arg(arg!(--json "Output in JSON")),
+        )
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
+}
+
+/// Runs the command given on the command line, printing the reports of a run after it.
+pub fn cli(matches: &ArgMatches) -> Result<()> {
+    if let Some(("diff", matches)) = matches.subcommand() {
+        let old = matches.get_one::<PathBuf>("OLD").unwrap();
+        let new = matches.get_one::<PathBuf>("NEW").unwrap();
+        diff::run(old, new, matches.get_flag("json")).context("failed to read record file")?;
+        return Ok(());
+    }
+
+    let config = Config::from_matches(matches);
+    if matches.get_flag("parse-only") {
+        let src = load_source(&config)?;
+        let list = config
+            .src
+            .as_deref()
+            .map(Path::new)
+            .filter(|path| path.is_file() && !metalink::is_metalink(path));
+        let errors = print_parse(&src, list, config.naming).context("failed to read URL list")?;
+        if errors != 0 {
+            bail!("{errors} error(s) in source");
+        }
+        return Ok(());
+    }
+    if matches.get_flag("list-hosts") {
+        print_hosts(&load_source(&config)?, config.files.iter());
+        return Ok(());
+    }
+    if matches.get_flag("coverage") {
+        let mut src = load_source(&config)?;
+        let originals = config.ignore_name_case.then(|| src.lowercase_names());
+        print_coverage(
+            &src,
+            config.files.iter(),
+            config.normalize_names,
+            originals.as_ref(),
+        );
+        return Ok(());
+    }
+
+    // Results go to stderr when the record is streamed to stdout.
+    let mut out: Box<dyn Write> = if config.rec == "-" {
+        Box::new(io::stderr())
+    } else {
+        Box::new(io::stdout())
+    };
+    let session = match matches.get_one::<PathBuf>("session") {
+        Some(path) => {
+            if config.rec == "-" {
+                bail!("a session can't be resumed from a record streamed to stdout");
+            }
+            let options = session_options(matches);
+            let progress = open_session(path, &options, &config.rec)?;
+            match (progress.runs, &progress.last) {
+                (0, _) => writeln!(out, "session: started ({})", path.display())?,
+                (runs, last) => {
+                    let last = last
+                        .as_ref()
+                        .map_or(String::new(), |last| format!(", last {last}"));
+                    writeln!(
+                        out,
+                        "session: resumed ({}) after {runs} run(s){last}: {}",
+                        path.display(),
+                        progress.counter
+                    )?;
+                }
+            }
+            Some((path, options, progress))
+        }
+        None => None,
+    };
+    let rec = config.rec.clone();
+    let summary_json = config.summary_json.clone();
+    let metrics_file = config.metrics_file.clone();
+    let sqlite = config.sqlite.clone();
+    let expect_count = config.expect_count;
+    let group_results = config.group_results;
+    let verify_dir_structure = config.verify_dir_structure;
+    let report = run(config, &mut out)?;
+
+    let counter = &report.counter;
+    writeln!(out, "finished: {counter}")?;
+    if let Some((path, options, mut progress)) = session {
+        progress.runs += 1;
+        progress.rec_len = fs::metadata(&rec)?.len();
+        progress.counter = counter.clone();
+        if let Some((name, _)) = report.outcomes.last() {
+            progress.last = Some(name.clone());
+        }
+        save_session(path, &options, &progress).context("failed to save session")?;
+    }
+    if let Some((good, total)) = report.coverage {
+        let percent = (good * 100).checked_div(total).unwrap_or(0);
+        writeln!(out, "coverage: {percent}% good ({good}/{total})")?;
+    }
+    if let Some(path) = &summary_json {
+        write_summary(path, &report).context("failed to write summary")?;
+    }
+    if let Some(path) = &metrics_file {
+        write_metrics(path, &report).context("failed to write metrics")?;
+    }
+    if let Some(path) = &sqlite {
+        export_sqlite(path, &report).context("failed to write SQLite database")?;
+    }
+    log::info!("finished: {counter}");
+
+    if let Some(hosts) = &report.hosts {
+        let mut hosts: Vec<_> = hosts.iter().collect();
+        hosts.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        writeln!(out, "hosts: {}", hosts.len())?;
+        for (host, stats) in hosts {
+            let counter = &stats.counter;
+            writeln!(
+                out,
+                "{host}: {} good, {} bad, {} error ({})",
+                counter.good,
+                counter.bad,
+                counter.error,
+                format_speed(stats.bytes, stats.secs)
+            )?;
+        }
+    }
+
+    if let Some(groups) = &report.dups {
+        writeln!(out, "duplicates: {} group(s)", groups.len())?;
+        for group in groups {
+            writeln!(out, "{}", group.join(", "))?;
+        }
+    }
+    if let Some(reclaimed) = report.reclaimed {
+        writeln!(out, "reclaimed: {reclaimed} byte(s)")?;
+    }
+    if group_results {
+        write_grouped(&mut out, &report.outcomes)?;
+    }
+    if verify_dir_structure {
+        write_structure(&mut out, &report.outcomes)?;
+    }
+
+    if report.error_limit_reached {
+        bail!("too many errors");
+    }
+    // Catches files of a managed collection missing from both the arguments and the record.
+    if let Some(expected) = expect_count {
+        let found = counter.total();
+        if found != expected {
+            writeln!(out, "error: expected {expected} files, found {found}")?;
+            bail!("file count mismatch");
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the files and probes the rest of the source as configured,
+/// printing the progress to `out` and writing the record along the way.
+pub fn run(config: Config, mut out: &mut dyn Write) -> Result<RunReport> {
+    // With no source at all, files are only checked against their names or a torrent.
+    let offline = config.src.is_none()
+        && config.src_api.is_none()
+        && config.src_index.is_none()
+        && config.remote_dir.is_none();
+    let from_stdin = config.src.as_deref() == Some("-");
+    if from_stdin && config.files.len() != 1 {
+        bail!("only one file can be compared with standard input");
+    }
+    if from_stdin && config.pause_before_availability {
+        bail!("--pause-before-availability cannot read its confirmation from standard input used by --src -");
+    }
+    // Rejected before the source and the record file are opened.
+    #[cfg(not(any(unix, windows)))]
+    if config.checkpoint_on_signal {
+        bail!("--checkpoint-on-signal is only supported on Unix and Windows");
+    }
+    #[cfg(not(unix))]
+    if config.check_sparse {
+        bail!("--check-sparse is only supported on Unix");
+    }
+    let mut src = load_source(&config)?;
+    let normalize = config.normalize_names;
+    let ignore_case = config.ignore_name_case;
+    let mut originals = if ignore_case {
+        src.lowercase_names()
+    } else {
+        HashMap::new()
+    };
+    // Taken before names are removed from the source as they are recorded or checked.
+    let src_names: Option<HashSet<String>> = match &src {
+        Source::List(map) if config.report_coverage_percent => Some(map.keys().cloned().collect()),
+        _ => None,
+    };
+
+    let digests = match &config.src {
+        Some(src) if config.hash_verify_remote_only => Some(
+            load_digests(src, config.naming, normalize, ignore_case)
+                .context("failed to read URL list")?,
+        ),
+        _ => None,
+    };
+    // A Metalink also gives mirrors to fall back on and digests to check local files against.
+    let metalink = config
+        .src
+        .as_deref()
+        .filter(|src| metalink::is_metalink(Path::new(src)));
+    let mirrors = match metalink {
+        Some(src) => load_mirrors(Path::new(src), normalize, ignore_case)
+            .context("failed to read URL list")?,
+        None => HashMap::new(),
+    };
+    let local_digests = match metalink {
+        Some(src) if !config.hash_verify_remote_only => Some(
+            load_digests(src, config.naming, normalize, ignore_case)
+                .context("failed to read URL list")?,
+        ),
+        _ => None,
+    };
+
+    let mut counter = Counter::default();
+
+    let rec = &config.rec[..];
+    let mut options = OpenOptions::new();
+
+    #[cfg(windows)]
+    options.share_mode(1);
+
+    let tty = config.tty;
+    let (mut rec, recorded): (Box<dyn Write>, _) = if rec == "-" {
+        (Box::new(io::stdout()), Recorded::default())
+    } else {
+        let mut rec_file = options
+            .clone()
+            .create(true)
+            .read(true)
+            .write(true)
+            .open(rec)
+            .context("failed to open record file")?;
+        let before_offset = if config.rec_offset {
+            load_rec_offset(rec, &mut rec_file, &mut counter)?
+        } else {
+            RecOffset::default()
+        };
+        src.remove_if(|name| before_offset.kinds.contains_key(&key_hash(name)));
+        let kinds = load_rec(
+            &mut rec_file,
+            &mut src,
+            &mut counter,
+            normalize,
+            ignore_case,
+        )?;
+        let recorded = Recorded {
+            kinds,
+            before_offset,
+        };
+        (Box::new(rec_file), recorded)
+    };
+
+    let run_id = Uuid::new_v4();
+    let started = Instant::now();
+    if config.tag_run {
+        rec = Box::new(Tagged {
+            inner: rec,
+            tag: format!(" [run {run_id}]"),
+        });
+    }
+
+    if let Some(path) = config.log_file.as_ref() {
+        logger::init(path, config.log_level, config.log_rotate)
+            .context("failed to open log file")?;
+        rec = Box::new(Logged::new(rec));
+        log::info!("started run {run_id}");
+    }
+
+    let mut rec = Outcomes {
+        inner: rec,
+        line: Vec::new(),
+        outcomes: Vec::new(),
+        times: Vec::new(),
+    };
+
+    let mut etag_store = match config.use_etags.as_ref() {
+        Some(path) => {
+            let mut file = options
+                .create(true)
+                .read(true)
+                .write(true)
+                .open(path)
+                .context("failed to open ETag store")?;
+            let etags = load_etags(&mut file)?;
+            Some((file, etags))
+        }
+        None => None,
+    };
+
+    writeln!(out, "run: {run_id}")?;
+    writeln!(out, "loaded: {counter}")?;
+
+    let s3 = if config.s3 {
+        Some(Signer::from_env().context("AWS credentials not found")?)
+    } else {
+        None
+    };
+    let query_signer = config
+        .sign_hmac
+        .clone()
+        .map(|(secret, param)| QuerySigner::new(secret, param));
+    let sign_query = |url: String| match &query_signer {
+        Some(signer) => signer.sign(&url),
+        None => url,
+    };
+
+    let progress_interval = config.progress_interval;
+
+    let checkpoint_requested = Arc::new(AtomicBool::new(false));
+    #[cfg(unix)]
+    if config.checkpoint_on_signal {
+        signal_hook::flag::register(signal_hook::consts::SIGUSR1, checkpoint_requested.clone())
+            .context("failed to register signal handler")?;
+    }
+    #[cfg(windows)]
+    if config.checkpoint_on_signal {
+        ctrl_break::register(checkpoint_requested.clone())
+            .context("failed to register console control handler")?;
+    }
+
+    let mut heartbeat = config
+        .heartbeat
+        .as_ref()
+        .map(|path| Heartbeat::new(path.clone(), config.heartbeat_interval, run_id));
+
+    let mut handle = Easy::new();
+    // Also needed to keep the heartbeat going during long transfers.
+    handle.progress(tty || heartbeat.is_some()).unwrap();
+    handle.follow_location(true).unwrap();
+    handle.unrestricted_auth(true).unwrap();
+    handle.cookie_file("").unwrap();
+    if let Some(user) = config.user.as_ref() {
+        handle.username(user).unwrap();
+    }
+    if let Some(pass) = config.pass.as_ref() {
+        handle.password(pass).unwrap();
+    }
+    if let Some(cert) = config.cert.as_ref() {
+        handle.ssl_cert(cert).unwrap();
+    }
+    if let Some(key) = config.key.as_ref() {
+        handle.ssl_key(key).unwrap();
+    }
+    if let Some(pass) = config.key_password.as_ref() {
+        handle.key_password(pass).unwrap();
+    }
+    for opt in &config.curl_opts {
+        opt.apply(&mut handle)
+            .context("failed to set curl option")?;
+    }
+    let first_byte = config.first_byte_timeout.map(FirstByteTimer::new);
+    if let Some(timer) = &first_byte {
+        let verbose = config.curl_opts.iter().rev().find_map(CurlOpt::verbose);
+        timer
+            .install(&mut handle, verbose.unwrap_or(false))
+            .unwrap();
+    }
+    let no_data = |e: &curl::Error| first_byte.as_ref().is_some_and(|timer| timer.aborted(e));
+
+    let origin = config.origin.as_deref();
+    if let Some(referer) = config.referer.as_ref() {
+        handle.referer(referer).unwrap();
+    }
+    if config.auto_referer {
+        handle.autoreferer(true).unwrap();
+    }
+    handle.http_headers(base_headers(origin)).unwrap();
+    // Transfers with their own header function check headers with a clone of the guard.
+    let redirect = (!config.redirect_allow_hosts.is_empty())
+        .then(|| RedirectGuard::new(&config.redirect_allow_hosts));
+    if let Some(guard) = &redirect {
+        let mut guard = guard.clone();
+        handle
+            .header_function(move |header| guard.check(header))
+            .unwrap();
+    }
+
+    // Files can't be checked without the session, so they are all recorded as errors.
+    let mut session_failed = false;
+    if let Some(url) = config.prewarm_url.as_ref() {
+        // Visited for the cookies it sets, e.g. to get past a consent page.
+        handle.url(url).unwrap();
+        let mut transfer = handle.transfer();
+        transfer.write_function(|data| Ok(data.len())).unwrap();
+        let res = transfer.perform();
+        drop(transfer);
+        let code = handle.response_code().unwrap();
+        let reason = match res {
+            Err(e) => Some(e.to_string()),
+            Ok(()) if !(200..300).contains(&code) => Some(format!("response code {code}")),
+            Ok(()) => None,
+        };
+        if let Some(reason) = reason {
+            writeln!(out, "session: failed to prewarm {url} ({reason})")?;
+            log::error!("failed to prewarm {url}: {reason}");
+            session_failed = true;
+        }
+    }
+
+    let fail_on_missing = config.fail_on_missing_source;
+    if fail_on_missing {
+        let mut missing = 0;
+        for path_str in &config.files {
+            let path = Path::new(path_str);
+            if !path.is_file() {
+                continue;
+            }
+            let name = file_name(path, normalize);
+            let key = lookup_key(&name, ignore_case);
+            if !recorded.contains_key(&key[..]) && !src.contains(&key) {
+                writeln!(out, "{name}: error: missing source")?;
+                writeln!(rec, "{name}: error: missing source")?;
+                missing += 1;
+            }
+        }
+        if missing != 0 {
+            bail!("{missing} file(s) missing source");
+        }
+    }
+
+    let mut dups = config.report_dups.then(HashMap::<_, Vec<&String>>::new);
+
+    let torrent = match config.torrent.as_ref() {
+        Some(path) => {
+            let torrent = Torrent::load(path).context("failed to load torrent")?;
+            let paths: Vec<_> = config
+                .files
+                .iter()
+                .map(Path::new)
+                .filter(|path| path.is_file())
+                .map(|path| (file_name(path, normalize), path))
+                .collect();
+            let locals = paths
+                .iter()
+                .map(|(name, path)| (&name[..], *path))
+                .collect();
+            Some(torrent.verify(&locals)?)
+        }
+        None => None,
+    };
+
+    let hash_cache = match config.hash_cache.as_ref() {
+        Some(dir) => Some(HashCache::new(dir.clone()).context("failed to create hash cache")?),
+        None => None,
+    };
+    let login = config.login_redirect_pattern.as_ref();
+    let range_map = match &config.range_map {
+        Some(path) => {
+            Some(load_range_map(path, normalize, ignore_case).context("failed to read range map")?)
+        }
+        None => None,
+    };
+    let absent = match config.expect_absent.as_ref() {
+        Some(path) => {
+            let list = fs::read_to_string(path).context("failed to read absent list")?;
+            let names = list.lines().map(str::trim).filter(|name| !name.is_empty());
+            let names = names.map(|name| {
+                if normalize {
+                    normalize_name(name, false)
+                } else {
+                    name.into()
+                }
+            });
+            Some(names.collect::<HashSet<String>>())
+        }
+        None => None,
+    };
+    let schedule = config.bandwidth_schedule.as_ref();
+    let mark_checking = config.mark_checking;
+    let empty_is_error = config.empty_is_error;
+    let cas = config.cas;
+    let hash = config.hash;
+    let explain = config.explain;
+    let dedup = config.dedup;
+    let mut cache = HashMap::<(String, Option<String>), PathBuf>::new();
+
+    let other_dir = config.other_dir.as_ref();
+    let remote_dir = config.remote_dir.as_ref();
+    let range_size = config.force_ranged;
+    let local_gzip = config.local_gzip;
+    let cache_url = config.cache_url.as_deref();
+    let retry_on_bad = config.retry_on_bad;
+    let mut jitter = config.retry_jitter.map(|fraction| {
+        let seed = config.seed.unwrap_or_else(|| {
+            let (high, low) = run_id.as_u64_pair();
+            high ^ low
+        });
+        Jitter::new(fraction, seed)
+    });
+    let strict_length = config.strict_length;
+    let decode = config.decode;
+    let judge = Judge {
+        decode,
+        check_sparse: config.check_sparse,
+        ignore_trailing_zeros: config.ignore_trailing_zeros,
+        on_growing: &config.on_growing,
+    };
+    let mut mirror_rank = match config.mirror_rank.clone() {
+        Some(path) => Some(
+            MirrorRank::load(path, config.reset_mirror_stats)
+                .context("failed to load mirror stats")?,
+        ),
+        None => None,
+    };
+    let plugin = match config.compare_plugin.as_ref() {
+        Some(path) => Some(Rc::new(
+            Plugin::load(path).context("failed to load comparison plugin")?,
+        )),
+        None => None,
+    };
+
+    let max_bytes = config.max_bytes;
+    let mut downloaded = 0;
+    let mut by_host = config
+        .report_by_host
+        .then(HashMap::<String, HostStats>::new);
+    let mut transfers = config.sqlite.is_some().then(HashMap::new);
+    let mut budget_reached = false;
+    // Shown along with the progress of downloads, which is only drawn on a terminal.
+    let mut eta = tty.then(|| {
+        let pending = config.files.iter().filter(|path_str| {
+            let path = Path::new(path_str);
+            path.is_file()
+                && !recorded.contains_key(&lookup_key(&file_name(path, normalize), ignore_case)[..])
+        });
+        Eta::new(pending.count() as u32)
+    });
+
+    let mut error_limit = config.max_errors.map(|max| ErrorLimit {
+        max,
+        consecutive: config.max_errors_consecutive,
+        errors: 0,
+        last: counter.clone(),
+    });
+    let mut error_limit_reached = false;
+
+    'files: for path_str in &config.files {
+        checkpoint(&checkpoint_requested, &counter, &mut out, &mut rec)?;
+        if max_bytes.is_some_and(|max| downloaded >= max) {
+            budget_reached = true;
+            break;
+        }
+        if let Some(limit) = &mut error_limit {
+            if limit.check(&counter, &mut out)? {
+                error_limit_reached = true;
+                break;
+            }
+        }
+        if let Some(heartbeat) = &mut heartbeat {
+            heartbeat
+                .beat(counter.total(), path_str)
+                .context("failed to update heartbeat")?;
+        }
+        let path = Path::new(path_str);
+        if !path.is_file() {
+            writeln!(out, "{path_str}: error: not a file")?;
+            log::warn!("{path_str}: not a file");
+            continue;
+        }
+
+        let name = &file_name(path, normalize)[..];
+        let key = &lookup_key(name, ignore_case)[..];
+        if recorded.contains_key(key) {
+            continue;
+        }
+        if session_failed {
+            writeln!(out, "{name}: error: session")?;
+            record(&mut counter, &mut rec, name, "error: session")?;
+            continue;
+        }
+        if let Some(eta) = &mut eta {
+            eta.start_file();
+        }
+        if let Some(dups) = &mut dups {
+            let digest = cas::hex_digest(Algo::Sha256, path, hash_cache.as_ref())?;
+            dups.entry(digest).or_default().push(path_str);
+        }
+        write!(out, "{name}: ")?;
+        out.flush()?;
+        if mark_checking {
+            writeln!(rec, "{name}: checking")?;
+        }
+
+        if empty_is_error && path.metadata()?.len() == 0 {
+            src.remove(key);
+            writeln!(out, "error: empty")?;
+            record(&mut counter, &mut rec, name, "error: empty")?;
+            continue;
+        }
+
+        if cas {
+            match cas::verify(path, name, hash, hash_cache.as_ref())? {
+                None => {
+                    src.remove(key);
+                    writeln!(out, "error: no digest in name")?;
+                    record(&mut counter, &mut rec, name, "error: no digest in name")?;
+                    continue;
+                }
+                Some(false) => {
+                    src.remove(key);
+                    writeln!(out, "bad: content does not match name")?;
+                    record(
+                        &mut counter,
+                        &mut rec,
+                        name,
+                        "bad: content does not match name",
+                    )?;
+                    continue;
+                }
+                Some(true) if offline => {
+                    writeln!(out, "good: content matches name")?;
+                    record(&mut counter, &mut rec, name, "good: content matches name")?;
+                    continue;
+                }
+                Some(true) => {}
+            }
+        }
+
+        // Files are then compared with the source, as with `--cas`.
+        if let Some((algo, expected)) = local_digests.as_ref().and_then(|d| d.get(key)) {
+            if cas::hex_digest(*algo, path, hash_cache.as_ref())? != *expected {
+                src.remove(key);
+                writeln!(out, "bad: content does not match digest")?;
+                record(
+                    &mut counter,
+                    &mut rec,
+                    name,
+                    "bad: content does not match digest",
+                )?;
+                continue;
+            }
+        }
+
+        if let Some(verdicts) = &torrent {
+            let status = match verdicts.get(name) {
+                None => Some("error: not in torrent".into()),
+                Some(Verdict::SizeMismatch) => Some("bad: length differs from torrent".into()),
+                Some(Verdict::Bad { failed, verified }) if config.partial_credit => {
+                    let matched = verified - failed.len();
+                    // Accepted files aren't compared with the source, which would only find them bad.
+                    let accepted = config
+                        .accept_threshold
+                        .is_some_and(|t| matched as f64 >= t * *verified as f64);
+                    if accepted {
+                        Some(format!("good: {matched}/{verified} chunks match"))
+                    } else {
+                        Some(format!("bad: {}/{verified} chunks failed", failed.len()))
+                    }
+                }
+                Some(Verdict::Bad { failed, .. }) => {
+                    let pieces: Vec<_> = failed.iter().map(|i| i.to_string()).collect();
+                    Some(format!("bad: failed piece(s): {}", pieces.join(", ")))
+                }
+                Some(Verdict::Unverified(n)) => {
+                    Some(format!("error: {n} piece(s) span missing files"))
+                }
+                Some(Verdict::Good) if offline => Some("good: pieces match torrent".into()),
+                Some(Verdict::Good) => None,
+            };
+            if let Some(status) = status {
+                src.remove(key);
+                writeln!(out, "{status}")?;
+                record(&mut counter, &mut rec, name, &status)?;
+                continue;
+            }
+        }
+
+        // Offline sources are read in place of a download.
+        let reference: Option<(Box<dyn Read>, String)> = match remote_dir {
+            Some(remote_dir) => {
+                let remote = remote_dir.join(name);
+                if !remote.is_file() {
+                    writeln!(out, "error: missing source")?;
+                    record(&mut counter, &mut rec, name, "error: missing source")?;
+                    continue;
+                }
+                let source = format!("{} (from remote directory)", remote.display());
+                Some((Box::new(File::open(&remote)?), source))
+            }
+            None if from_stdin => Some((Box::new(io::stdin()), "- (from standard input)".into())),
+            None => None,
+        };
+        if let Some((mut reference, source)) = reference {
+            let mut cmp = Comparator::new(open_local(path, local_gzip)?, plugin.clone());
+            let mut buf = vec![0; 65536];
+            let mut decoder = decode.map(Decoder::new);
+            let mut decoded = Vec::new();
+            loop {
+                match reference.read(&mut buf)? {
+                    0 => break,
+                    n => cmp.update(feed(&mut decoder, &mut decoded, &buf[..n])),
+                }
+            }
+            let valid = finish_decoding(&mut decoder, [&mut cmp]);
+            let judged = judge.judge(&mut cmp, path, valid)?;
+            writeln!(out, "{}", judged.status)?;
+            if explain {
+                writeln!(out, "  source: {source}")?;
+                judged.explain(&mut out)?;
+            }
+            record(&mut counter, &mut rec, name, judged.status)?;
+            continue;
+        }
+
+        let url = match src.provide(name, key) {
+            Some(url) => url,
+            None => {
+                writeln!(out, "error: missing source")?;
+                if explain {
+                    writeln!(out, "  verdict: no entry in the URL list has this name")?;
+                }
+                writeln!(rec, "{name}: error: missing source")?;
+                if fail_on_missing {
+                    bail!("missing source for {name}");
+                }
+                counter.error += 1;
+                continue;
+            }
+        };
+        let mut source = format!("  source: {url} (from {})", src.describe());
+        let (url, slice) = split_slice(&url);
+        let mut url = url.to_string();
+        let mut from_cache = false;
+        if let Some(base) = cache_url {
+            let cached = format!("{}/{name}", base.trim_end_matches('/'));
+            if cache_has(&mut handle, &cached, origin) {
+                source = format!("  source: {cached} (cached copy of {url})");
+                url = cached;
+                from_cache = true;
+            }
+        }
+        // The mirrors of a Metalink are fallen back on in priority order when a request fails.
+        let mut urls: Vec<_> = iter::once(url)
+            .chain(mirrors.get(key).into_iter().flatten().cloned())
+            .collect();
+        // A cached copy is still tried first.
+        if let Some(rank) = mirror_rank
+            .as_ref()
+            .filter(|_| !from_cache && urls.len() > 1)
+        {
+            let first = urls[0].clone();
+            rank.sort(&mut urls);
+            if urls[0] != first {
+                source = format!(
+                    "  source: {} (best mirror from {})",
+                    urls[0],
+                    src.describe()
+                );
+            }
+        }
+        let mirror = Cell::new(0);
+        if let Some(schedule) = &schedule {
+            handle.max_recv_speed(schedule.current_limit()).unwrap();
+        }
+
+        let stored_etag = etag_store
+            .as_ref()
+            .and_then(|(_, etags)| etags.get(name))
+            .cloned();
+        let prepare = |handle: &mut Easy, method, range: Option<(u64, u64)>| {
+            let mut headers = base_headers(origin);
+            if let Some((start, end)) = range {
+                headers
+                    .append(&format!("Range: bytes={start}-{end}"))
+                    .unwrap();
+            }
+            if let Some(etag) = &stored_etag {
+                headers.append(&format!("If-None-Match: {etag}")).unwrap();
+            }
+            let url = &urls[mirror.get()];
+            let url = match &s3 {
+                Some(s3) => s3.sign(method, url, &mut headers),
+                None => sign_query(url.clone()),
+            };
+            handle.http_headers(headers).unwrap();
+            handle.url(&url).unwrap();
+        };
+
+        // Slices of the same remote file share its effective URL and ETag.
+        let dedup = dedup && slice.is_none();
+        if dedup && !cache.is_empty() {
+            let mut etag = None;
+            let mut redirect = redirect.clone();
+            prepare(&mut handle, "HEAD", None);
+            handle.nobody(true).unwrap();
+            let mut transfer = handle.transfer();
+            transfer
+                .header_function(|header| {
+                    parse_etag(&mut etag, header);
+                    redirect.as_mut().is_none_or(|guard| guard.check(header))
+                })
+                .unwrap();
+            let res = transfer.perform();
+            drop(transfer);
+            handle.nobody(false).unwrap();
+
+            if res.is_ok() {
+                let eff_url = handle.effective_url().unwrap().unwrap();
+                if let Some(other) = cache.get(&(eff_url.into(), etag)) {
+                    let other_str = other.display();
+                    let good = if same_content(path, other)? {
+                        counter.good += 1;
+                        "good"
+                    } else {
+                        counter.bad += 1;
+                        "bad"
+                    };
+                    writeln!(out, "{good}: same as {other_str}")?;
+                    if explain {
+                        writeln!(out, "{source}")?;
+                        writeln!(
+                            out,
+                            "  verdict: effective URL and ETag match those of {other_str}, \
+                             which was good, so the files were compared locally"
+                        )?;
+                    }
+                    writeln!(rec, "{name}: {good}: same as {other_str}")?;
+                    continue;
+                }
+            }
+        }
+
+        // Mapped files are only checked in their regions, relative to a slice if any.
+        if let Some(regions) = range_map.as_ref().and_then(|map| map.get(key)) {
+            let base = slice.map_or(0, |(start, _)| start);
+            let mut guard = redirect.clone();
+            let mut status = "good".to_string();
+            let mut verdict = format!("  verdict: {} region(s) match", regions.len());
+            for &(start, end) in regions {
+                let mut file = File::open(path)?;
+                file.seek(SeekFrom::Start(start))?;
+                let mut cmp = Comparator::new(Box::new(file.take(end - start + 1)), plugin.clone());
+                prepare(&mut handle, "GET", Some((base + start, base + end)));
+                let mut transfer = handle.transfer();
+                transfer
+                    .write_function(|data| {
+                        cmp.update(data);
+                        Ok(data.len())
+                    })
+                    .unwrap();
+                transfer
+                    .header_function(|header| guard.as_mut().is_none_or(|g| g.check(header)))
+                    .unwrap();
+                let res = transfer.perform();
+                drop(transfer);
+                downloaded += cmp.received;
+
+                let blocked = guard.as_ref().and_then(RedirectGuard::take_blocked);
+                let code = handle.response_code().unwrap();
+                if let Some(location) = blocked.filter(|_| res.is_err()) {
+                    status = "error: redirect blocked".into();
+                    verdict = format!(
+                        "  verdict: redirected to {location}, whose host isn't in the allowlist"
+                    );
+                } else if let Err(e) = res {
+                    status = if no_data(&e) {
+                        "error: no data".into()
+                    } else {
+                        format!("error: {}: {e}", error_kind(&mut handle, &e))
+                    };
+                    verdict = "  verdict: the request failed before a response was complete".into();
+                } else if code == 304 {
+                    verdict =
+                        "  verdict: the ETag stored when the file was last good still matches"
+                            .into();
+                    break;
+                } else if code == 416 {
+                    status = format!("bad: range {start}-{end}");
+                    verdict = format!(
+                        "  verdict: bytes {start}-{end} are past the end of the remote file"
+                    );
+                } else if code != 206 {
+                    status = "error: range not served".into();
+                    verdict = format!(
+                        "  verdict: bytes {start}-{end} were requested, but the response code is {code}"
+                    );
+                } else if cmp.finish().is_some() {
+                    status = format!("bad: range {start}-{end}");
+                    verdict = format!("  verdict: bytes {start}-{end} differ");
+                } else {
+                    continue;
+                }
+                break;
+            }
+            count_status(&mut counter, status_kind(&status));
+            writeln!(out, "{status}")?;
+            if explain {
+                writeln!(out, "{source}")?;
+                writeln!(out, "{verdict}")?;
+            }
+            writeln!(rec, "{name}: {status}")?;
+            continue;
+        }
+
+        // Paginated files are assembled from their pages, which can't be requested in ranges.
+        if let Some(first) = config.paginate {
+            let mut cmp = Comparator::new(open_local(path, local_gzip)?, plugin.clone());
+            let mut guard = redirect.clone();
+            handle.http_headers(base_headers(origin)).unwrap();
+            let res = paginate::fetch(
+                &mut handle,
+                &urls[0],
+                first,
+                sign_query,
+                &mut guard,
+                &mut cmp,
+            );
+            downloaded += cmp.received;
+            let pages = match res {
+                Ok(pages) => pages,
+                Err(e) => {
+                    writeln!(out, "error: pagination")?;
+                    if explain {
+                        writeln!(out, "{source}")?;
+                        writeln!(
+                            out,
+                            "  verdict: page {} failed after {} byte(s) were received ({})",
+                            e.page, cmp.received, e.reason
+                        )?;
+                    }
+                    record(&mut counter, &mut rec, name, "error: pagination")?;
+                    continue;
+                }
+            };
+            // Pages are compared as they are, so they are always valid.
+            let judged = judge.judge(&mut cmp, path, true)?;
+            writeln!(out, "{}", judged.status)?;
+            if explain {
+                writeln!(out, "{source}")?;
+                let received = cmp.received;
+                writeln!(out, "  pages: {pages} page(s), {received} byte(s) received")?;
+                judged.explain(&mut out)?;
+            }
+            record(&mut counter, &mut rec, name, judged.status)?;
+            continue;
+        }
+
+        let mut retries = 0;
+        'attempt: loop {
+            let mut cmp = Comparator::new(open_local(path, local_gzip)?, plugin.clone());
+            // The decompressed length isn't known up front, so no progress is shown for it.
+            let len = if local_gzip {
+                0
+            } else {
+                fs::metadata(path)?.len()
+            };
+            // The copy of the file in the other directory, compared with the same download.
+            let other = other_dir.map(|dir| dir.join(path.file_name().unwrap()));
+            let mut other_cmp = match &other {
+                Some(other) if other.is_file() => Some(Comparator::new(
+                    open_local(other, local_gzip)?,
+                    plugin.clone(),
+                )),
+                _ => None,
+            };
+            let mut etag = None;
+            let mut ranges = 0;
+            // The first response whose Content-Length differs from its body, with `--strict-length`.
+            let mut length_mismatch = None;
+            let mut drawn = false;
+            let start = Instant::now();
+            let mut last_drawn = start;
+            let before = counter.clone();
+            let mut guard = redirect.clone();
+            let mut decoder = decode.map(Decoder::new);
+            let mut decoded = Vec::new();
+            let mut encoded = 0;
+            // A redirect blocked while looking for a cached copy is left unreported.
+            if let Some(guard) = &guard {
+                guard.take_blocked();
+            }
+
+            let res = loop {
+                let offset = cmp.received;
+                let range = match (slice, range_size) {
+                    (None, None) => None,
+                    (None, Some(size)) => Some((offset, offset + size - 1)),
+                    (Some((start, len)), size) => {
+                        let (start, end) = (start + offset, start + len - 1);
+                        Some((start, size.map_or(end, |size| end.min(start + size - 1))))
+                    }
+                };
+                prepare(&mut handle, "GET", range);
+
+                let mut total = None;
+                let mut claimed = None;
+                // Bytes received in the response, before decoding.
+                let mut raw = 0;
+                let mut transfer = handle.transfer();
+                transfer
+                    .write_function(|data| {
+                        raw += data.len() as u64;
+                        let n = data.len();
+                        let data = feed(&mut decoder, &mut decoded, data);
+                        cmp.update(data);
+                        if let Some(other_cmp) = &mut other_cmp {
+                            other_cmp.update(data);
+                        }
+                        Ok(n)
+                    })
+                    .unwrap();
+                transfer
+                    .header_function(|header| {
+                        parse_etag(&mut etag, header);
+                        parse_total_len(&mut total, header);
+                        if strict_length {
+                            parse_content_len(&mut claimed, header);
+                        }
+                        guard.as_mut().is_none_or(|guard| guard.check(header))
+                    })
+                    .unwrap();
+                transfer
+                    .progress_function(|_, now, _, _| {
+                        if let Some(heartbeat) = &mut heartbeat {
+                            let _ = heartbeat.beat(counter.total(), path_str);
+                        }
+                        if tty && last_drawn.elapsed() >= progress_interval {
+                            // Encoded bytes don't count towards the length of the local file.
+                            let percent = (len != 0 && decode.is_none()).then(|| {
+                                let percent = (offset as f64 + now) / len as f64 * 100.0;
+                                format!("{percent:.0}%")
+                            });
+                            let eta = eta.as_ref().and_then(Eta::describe);
+                            let progress: Vec<_> = percent.into_iter().chain(eta).collect();
+                            if !progress.is_empty() {
+                                let _ = write!(out, "\r{name}: {}\x1b[K", progress.join(", "));
+                                let _ = out.flush();
+                                last_drawn = Instant::now();
+                                drawn = true;
+                            }
+                        }
+                        first_byte.as_ref().is_none_or(FirstByteTimer::check)
+                    })
+                    .unwrap();
+
+                let res = transfer.perform();
+                drop(transfer);
+                ranges += 1;
+                downloaded += raw;
+                encoded += raw;
+                if let Some(claimed) = claimed.filter(|&claimed| claimed != raw) {
+                    length_mismatch.get_or_insert((claimed, raw));
+                }
+
+                // Request the next range until one comes back short or reaches the end.
+                let more = match (res.is_ok(), range_size) {
+                    (true, Some(size)) => {
+                        handle.response_code().unwrap() == 206
+                            && cmp.received - offset == size
+                            && match slice {
+                                Some((_, len)) => cmp.received < len,
+                                None => total.is_none_or(|total| cmp.received < total),
+                            }
+                    }
+                    _ => false,
+                };
+                if !more {
+                    break res;
+                }
+            };
+            if let Some(rank) = &mut mirror_rank {
+                let url = &urls[mirror.get()];
+                match res {
+                    Ok(()) if handle.response_code().unwrap() < 400 => {
+                        rank.succeeded(url, encoded, start.elapsed())
+                    }
+                    _ => rank.failed(url),
+                }
+            }
+            if drawn {
+                write!(out, "\r{name}: \x1b[K")?;
+            }
+            if let Some(e) = res.as_ref().err().filter(|e| e.is_partial_file()) {
+                // The server broke its own promise, so the local file isn't to blame.
+                writeln!(out, "error: truncated response")?;
+                if explain {
+                    writeln!(out, "{source}")?;
+                    let claimed = handle.content_length_download().unwrap();
+                    writeln!(
+                        out,
+                        "  verdict: the response ended before the {claimed} byte(s) given in Content-Length ({e})"
+                    )?;
+                }
+                record(&mut counter, &mut rec, name, "error: truncated response")?;
+                if let Some(hosts) = &mut by_host {
+                    host_stats(hosts, &mut handle).add(&counter, &before, cmp.received, start);
+                }
+                continue 'files;
+            }
+            let blocked = guard.as_ref().and_then(RedirectGuard::take_blocked);
+            if let Some(location) = blocked.filter(|_| res.is_err()) {
+                writeln!(out, "error: redirect blocked")?;
+                if explain {
+                    writeln!(out, "{source}")?;
+                    writeln!(
+                        out,
+                        "  verdict: redirected to {location}, whose host isn't in the allowlist"
+                    )?;
+                }
+                record(&mut counter, &mut rec, name, "error: redirect blocked")?;
+                if let Some(hosts) = &mut by_host {
+                    host_stats(hosts, &mut handle).add(&counter, &before, cmp.received, start);
+                }
+                continue 'files;
+            }
+            if let Err(e) = res {
+                let (status, verdict) = if no_data(&e) {
+                    let verdict = "the server sent nothing within --first-byte-timeout";
+                    ("error: no data".into(), verdict)
+                } else {
+                    let kind = error_kind(&mut handle, &e);
+                    let verdict = "the request failed before a response was complete";
+                    (format!("error: {kind}: {e}"), verdict)
+                };
+                if let Some(next) = urls.get(mirror.get() + 1) {
+                    writeln!(out, "{status}, trying the next mirror")?;
+                    log::info!("{name}: {status}, trying {next}");
+                    mirror.set(mirror.get() + 1);
+                    source = format!("  source: {next} (mirror from {})", src.describe());
+                    write!(out, "{name}: ")?;
+                    continue 'attempt;
+                }
+                writeln!(out, "{status}")?;
+                if explain {
+                    writeln!(out, "{source}")?;
+                    writeln!(out, "  verdict: {verdict}")?;
+                }
+                record(&mut counter, &mut rec, name, &status)?;
+                if let Some(hosts) = &mut by_host {
+                    host_stats(hosts, &mut handle).add(&counter, &before, cmp.received, start);
+                }
+                continue 'files;
+            }
+
+            let code = handle.response_code().unwrap();
+            let eff_url = handle.effective_url().unwrap().unwrap();
+            if login.is_some_and(|login| login.is_match(eff_url)) {
+                writeln!(out, "error: auth required")?;
+                if explain {
+                    writeln!(out, "{source}")?;
+                    writeln!(
+                        out,
+                        "  verdict: redirected to {eff_url}, which matches the login redirect pattern"
+                    )?;
+                }
+                record(&mut counter, &mut rec, name, "error: auth required")?;
+            } else if code == 403 && query_signer.is_some() {
+                writeln!(out, "error: 403")?;
+                if explain {
+                    writeln!(out, "{source}")?;
+                    writeln!(
+                        out,
+                        "  verdict: the signed request was refused, so the signature may be wrong"
+                    )?;
+                }
+                record(&mut counter, &mut rec, name, "error: 403")?;
+            } else if code == 416 && (slice.is_some() || range_size.is_some()) {
+                writeln!(out, "bad: too short for range")?;
+                if explain {
+                    writeln!(out, "{source}")?;
+                    writeln!(
+                        out,
+                        "  verdict: a byte range was requested past the end of the remote file"
+                    )?;
+                }
+                record(&mut counter, &mut rec, name, "bad: too short for range")?;
+            } else if slice.is_some() && code != 206 && code != 304 {
+                // The whole remote file is never what a slice should be compared with.
+                writeln!(out, "error: range not served")?;
+                if explain {
+                    writeln!(out, "{source}")?;
+                    writeln!(
+                        out,
+                        "  verdict: a byte range was requested, but the response code is {code}"
+                    )?;
+                }
+                record(&mut counter, &mut rec, name, "error: range not served")?;
+            } else if code == 206 && range_size.is_none() && slice.is_none() {
+                // No range was requested, so the body can't be compared with the whole file.
+                writeln!(out, "error: unexpected partial content")?;
+                if explain {
+                    writeln!(out, "{source}")?;
+                    writeln!(
+                        out,
+                        "  verdict: the server sent a range that was never requested"
+                    )?;
+                }
+                record(
+                    &mut counter,
+                    &mut rec,
+                    name,
+                    "error: unexpected partial content",
+                )?;
+            } else if code == 304 {
+                writeln!(out, "good: not modified")?;
+                if explain {
+                    writeln!(out, "{source}")?;
+                    writeln!(
+                        out,
+                        "  verdict: the ETag stored when the file was last good still matches"
+                    )?;
+                }
+                record(&mut counter, &mut rec, name, "good")?;
+            } else if let Some((claimed, delivered)) = length_mismatch {
+                writeln!(out, "error: length mismatch")?;
+                if explain {
+                    writeln!(out, "{source}")?;
+                    writeln!(
+                        out,
+                        "  verdict: Content-Length gave {claimed} byte(s), but {delivered} were received"
+                    )?;
+                }
+                record(&mut counter, &mut rec, name, "error: length mismatch")?;
+            } else {
+                let valid =
+                    finish_decoding(&mut decoder, iter::once(&mut cmp).chain(&mut other_cmp));
+                let judged = judge.judge(&mut cmp, path, valid)?;
+                let first_diff = judged.first_diff;
+                let good = judged.status;
+                // Only files matching in full are good for later runs to rely on.
+                if first_diff.is_none() && good == "good" {
+                    if let (Some((file, _)), Some(etag)) = (&mut etag_store, &etag) {
+                        writeln!(file, "{name}: {etag}")?;
+                    }
+                    if dedup {
+                        let eff_url = handle.effective_url().unwrap().unwrap();
+                        cache.insert((eff_url.into(), etag), path.into());
+                    }
+                }
+                // A mirror may serve a corrupt copy for a moment while being updated.
+                if good == "bad" && retries < retry_on_bad {
+                    retries += 1;
+                    let delay = match &mut jitter {
+                        Some(jitter) => jitter.apply(config.delay),
+                        None => config.delay,
+                    };
+                    log::info!(
+                        "{name}: bad, retrying in {:.1}s ({retries}/{retry_on_bad})",
+                        delay.as_secs_f64()
+                    );
+                    thread::sleep(delay);
+                    continue 'attempt;
+                }
+                count_status(&mut counter, status_kind(good));
+                let other_diff = other_cmp.as_mut().map(Comparator::finish);
+                let three_way = other.is_some().then_some(match (first_diff, other_diff) {
+                    (_, None) => "right-missing",
+                    (None, Some(None)) => "both-good",
+                    (None, Some(Some(_))) => "left-good",
+                    (Some(_), Some(None)) => "right-good",
+                    (Some(_), Some(Some(_))) => "both-bad",
+                });
+                let speed = format_speed(cmp.received, start.elapsed().as_secs_f64());
+                match three_way {
+                    Some(three_way) => writeln!(out, "{good}: {three_way} ({speed})")?,
+                    None => writeln!(out, "{good} ({speed})")?,
+                }
+                if explain {
+                    writeln!(out, "{source}")?;
+                    let eff_url = handle.effective_url().unwrap().unwrap();
+                    writeln!(out, "  response: {code} from {eff_url}")?;
+                    if range_size.is_some() {
+                        writeln!(out, "  ranges: {ranges} request(s)")?;
+                    }
+                    if retries != 0 {
+                        writeln!(out, "  retries: {retries} after being bad")?;
+                    }
+                    let received = cmp.received;
+                    if local_gzip {
+                        writeln!(
+                            out,
+                            "  compared: {received} byte(s) received, local decompressed"
+                        )?;
+                    } else {
+                        writeln!(out, "  compared: {received} byte(s) received, {len} local")?;
+                    }
+                    if decode.is_some() {
+                        writeln!(out, "  decoded: {encoded} byte(s) before decoding")?;
+                    }
+                    judged.explain(&mut out)?;
+                    if let Some(other) = &other {
+                        let other = other.display();
+                        match other_diff {
+                            None => writeln!(out, "  other: {other} is missing")?,
+                            Some(None) => writeln!(out, "  other: {other} matches")?,
+                            Some(Some(offset)) => {
+                                writeln!(out, "  other: {other} first differs at byte {offset}")?
+                            }
+                        }
+                    }
+                }
+                match three_way {
+                    Some(three_way) => writeln!(rec, "{name}: {good}: {three_way}")?,
+                    None => writeln!(rec, "{name}: {good}")?,
+                }
+            }
+            if let Some(hosts) = &mut by_host {
+                host_stats(hosts, &mut handle).add(&counter, &before, cmp.received, start);
+            }
+            if let Some(transfers) = &mut transfers {
+                transfers.insert(name.to_string(), (cmp.received, start.elapsed()));
+            }
+            break;
+        }
+    }
+
+    handle.nobody(true).unwrap();
+    handle.http_headers(base_headers(origin)).unwrap();
+
+    let confirm = config.availability_confirm;
+    let delay = config.delay;
+    let pause = config.pause_before_availability;
+    let content_type = config.expect_content_type.as_ref();
+    let soft_404_pattern = config.soft_404_pattern.as_ref();
+    let prune_dir = config.prune_na.as_ref();
+    let dry_run = config.dry_run;
+
+    if budget_reached {
+        writeln!(
+            out,
+            "stopped: downloaded {downloaded} byte(s), leaving the rest for the next run"
+        )?;
+    }
+    let stopped = budget_reached || error_limit_reached || session_failed;
+
+    let mut absent_rest = Vec::new();
+    if let Some(absent) = absent.as_ref().filter(|_| !stopped) {
+        // Names that were compared as local files already have their results.
+        let local: HashSet<_> = config
+            .files
+            .iter()
+            .map(|path| {
+                lookup_key(&file_name(Path::new(path), normalize), ignore_case).into_owned()
+            })
+            .collect();
+        let mut absent: Vec<_> = absent
+            .iter()
+            .filter(|name| {
+                let key = lookup_key(name, ignore_case);
+                !recorded.contains_key(&key[..]) && !local.contains(&key[..])
+            })
+            .collect();
+        absent.sort_unstable();
+        for name in absent {
+            match src.provide(name, &lookup_key(name, ignore_case)) {
+                Some(url) => absent_rest.push((name.clone(), url)),
+                None => {
+                    writeln!(out, "{name}: error: missing source")?;
+                    record(&mut counter, &mut rec, name, "error: missing source")?;
+                }
+            }
+        }
+    }
+
+    // Unchecked files are still in the list, so nothing is probed after stopping.
+    let mut rest: Vec<_> = if stopped {
+        Vec::new()
+    } else {
+        src.into_rest()
+            .map(|(key, url)| (originals.remove(&key).unwrap_or(key), url))
+            .chain(absent_rest)
+            .collect()
+    };
+
+    // Names with digests are hashed as downloaded instead of probed for availability.
+    if let Some(digests) = &digests {
+        handle.nobody(false).unwrap();
+        for (name, url) in mem::take(&mut rest) {
+            checkpoint(&checkpoint_requested, &counter, &mut out, &mut rec)?;
+            if let Some(limit) = &mut error_limit {
+                if limit.check(&counter, &mut out)? {
+                    error_limit_reached = true;
+                    break;
+                }
+            }
+            if let Some(heartbeat) = &mut heartbeat {
+                heartbeat
+                    .beat(counter.total(), &name)
+                    .context("failed to update heartbeat")?;
+            }
+            write!(out, "{name}: ")?;
+            out.flush()?;
+
+            let Some((algo, expected)) = digests.get(&lookup_key(&name, ignore_case)[..]) else {
+                writeln!(out, "error: no digest")?;
+                record(&mut counter, &mut rec, &name, "error: no digest")?;
+                continue;
+            };
+            let (url, slice) = split_slice(&url);
+            let mut headers = base_headers(origin);
+            if let Some((start, len)) = slice {
+                headers
+                    .append(&format!("Range: bytes={start}-{}", start + len - 1))
+                    .unwrap();
+            }
+            let url = match &s3 {
+                Some(s3) => s3.sign("GET", url, &mut headers),
+                None => sign_query(url.into()),
+            };
+            handle.http_headers(headers).unwrap();
+            handle.url(&url).unwrap();
+
+            let mut hasher = algo.hasher();
+            let mut guard = redirect.clone();
+            let mut transfer = handle.transfer();
+            transfer
+                .write_function(|data| {
+                    hasher.update(data);
+                    Ok(data.len())
+                })
+                .unwrap();
+            transfer
+                .header_function(|header| guard.as_mut().is_none_or(|guard| guard.check(header)))
+                .unwrap();
+            let res = transfer.perform();
+            drop(transfer);
+
+            let code = handle.response_code().unwrap();
+            let blocked = guard.as_ref().and_then(RedirectGuard::take_blocked);
+            let actual = hasher.finish_hex();
+            let status = match res {
+                Err(_) if blocked.is_some() => "error: redirect blocked".into(),
+                Err(e) if no_data(&e) => "error: no data".into(),
+                Err(e) => format!("error: {}: {e}", error_kind(&mut handle, &e)),
+                Ok(()) if slice.is_some() && code != 206 => "error: range not served".into(),
+                Ok(()) if !(200..300).contains(&code) => format!("error: response code {code}"),
+                Ok(()) if actual == *expected => "good".into(),
+                Ok(()) => "bad".into(),
+            };
+            count_status(&mut counter, status_kind(&status));
+            writeln!(out, "{status}")?;
+            if explain {
+                writeln!(out, "  source: {url} (from URL list)")?;
+                if status_kind(&status) != "error" {
+                    writeln!(out, "  digest: expected {expected}, received {actual}")?;
+                }
+            }
+            writeln!(rec, "{name}: {status}")?;
+        }
+    }
+    if pause && !rest.is_empty() {
+        writeln!(out, "compared: {counter}")?;
+        write!(
+            out,
+            "{} name(s) left to probe for availability, press Enter to continue or q to stop: ",
+            rest.len()
+        )?;
+        out.flush()?;
+        let mut line = String::new();
+        // Names left unprobed are probed again on the next run.
+        if io::stdin().read_line(&mut line)? == 0 || line.trim() == "q" {
+            rest.clear();
+        }
+    }
+
+    for (name, url) in rest {
+        let url = split_slice(&url).0.to_string();
+        checkpoint(&checkpoint_requested, &counter, &mut out, &mut rec)?;
+        if let Some(limit) = &mut error_limit {
+            if limit.check(&counter, &mut out)? {
+                error_limit_reached = true;
+                break;
+            }
+        }
+        if let Some(heartbeat) = &mut heartbeat {
+            heartbeat
+                .beat(counter.total(), &name)
+                .context("failed to update heartbeat")?;
+        }
+        write!(out, "{name}: ")?;
+        out.flush()?;
+
+        // The mirrors of a Metalink are fallen back on in priority order when a request fails.
+        let mut urls = iter::once(url)
+            .chain(
+                mirrors
+                    .get(&lookup_key(&name, ignore_case)[..])
+                    .into_iter()
+                    .flatten()
+                    .cloned(),
+            )
+            .peekable();
+        let probe = loop {
+            let url = match &s3 {
+                Some(s3) => {
+                    let mut headers = base_headers(origin);
+                    let url = s3.sign("HEAD", &urls.next().unwrap(), &mut headers);
+                    handle.http_headers(headers).unwrap();
+                    url
+                }
+                None => sign_query(urls.next().unwrap()),
+            };
+
+            let redirect = redirect.as_ref();
+            let mut probe = Probe::run(
+                &mut handle,
+                &url,
+                &name,
+                normalize,
+                login,
+                content_type,
+                redirect,
+            );
+            for _ in 1..confirm {
+                thread::sleep(delay);
+                let again = Probe::run(
+                    &mut handle,
+                    &url,
+                    &name,
+                    normalize,
+                    login,
+                    content_type,
+                    redirect,
+                );
+                if !again.agrees_with(&probe) {
+                    probe = Probe::Inconclusive;
+                    break;
+                }
+            }
+            // Only the files a HEAD found available are worth the bytes of a body.
+            if let (Probe::Available, Some(pattern)) = (&probe, soft_404_pattern) {
+                probe = match is_soft_404(&mut handle, &url, origin, pattern) {
+                    Ok(true) => Probe::NotAvailable,
+                    Ok(false) => Probe::Available,
+                    Err(e) => Probe::Error(error_kind(&mut handle, &e), e),
+                };
+            }
+            match (&probe, urls.peek()) {
+                (Probe::Error(_, e), Some(next)) => log::info!("{name}: {e}, trying {next}"),
+                _ => break probe,
+            }
+        };
+
+        match probe {
+            Probe::Error(_, e) if no_data(&e) => {
+                writeln!(out, "error: no data")?;
+                record(&mut counter, &mut rec, &name, "error: no data")?;
+            }
+            Probe::Error(kind, e) => {
+                let status = format!("error: {kind}: {e}");
+                writeln!(out, "{status}")?;
+                record(&mut counter, &mut rec, &name, &status)?;
+            }
+            Probe::Available if absent.as_ref().is_some_and(|absent| absent.contains(&name)) => {
+                writeln!(out, "error: should be absent")?;
+                record(&mut counter, &mut rec, &name, "error: should be absent")?;
+            }
+            Probe::Available => {
+                writeln!(out, "error: available")?;
+                record(&mut counter, &mut rec, &name, "error: available")?;
+            }
+            Probe::AuthRequired => {
+                writeln!(out, "error: auth required")?;
+                record(&mut counter, &mut rec, &name, "error: auth required")?;
+            }
+            // Refused signatures would otherwise pass for missing files.
+            Probe::NotAvailable
+                if query_signer.is_some() && handle.response_code().unwrap() == 403 =>
+            {
+                writeln!(out, "error: 403")?;
+                record(&mut counter, &mut rec, &name, "error: 403")?;
+            }
+            Probe::RedirectBlocked => {
+                writeln!(out, "error: redirect blocked")?;
+                record(&mut counter, &mut rec, &name, "error: redirect blocked")?;
+            }
+            Probe::NotAvailable => {
+                writeln!(out, "n/a")?;
+                writeln!(rec, "{name}: n/a")?;
+                counter.na += 1;
+                // Names with separators could point outside the directory.
+                let path = prune_dir
+                    .filter(|_| Path::new(&name).file_name() == Some(name.as_ref()))
+                    .map(|dir| dir.join(&name))
+                    .filter(|path| path.is_file());
+                if let Some(path) = path {
+                    if dry_run {
+                        writeln!(out, "  would prune: {}", path.display())?;
+                    } else {
+                        fs::remove_file(&path)
+                            .with_context(|| format!("failed to prune {}", path.display()))?;
+                        writeln!(out, "  pruned: {}", path.display())?;
+                        log::info!("pruned {}", path.display());
+                    }
+                }
+            }
+            // Left unrecorded to be probed again on the next run.
+            Probe::Inconclusive => {
+                writeln!(out, "inconclusive")?;
+                log::info!("{name}: inconclusive");
+            }
+        }
+    }
+
+    if let Some(heartbeat) = &mut heartbeat {
+        heartbeat
+            .force(counter.total(), "(finished)")
+            .context("failed to update heartbeat")?;
+    }
+    if let Some(rank) = &mirror_rank {
+        rank.save().context("failed to save mirror stats")?;
+    }
+    if config.rec_offset && config.rec != "-" {
+        rec.flush()?;
+        // Only the lines appended since the saved offset are new to the keys file.
+        let kinds = recorded
+            .kinds
+            .iter()
+            .map(|(key, kind)| (key_hash(key), &kind[..]))
+            .chain(rec.outcomes.iter().map(|(name, status)| {
+                (
+                    key_hash(&lookup_key(name, ignore_case)),
+                    status_kind(status),
+                )
+            }));
+        save_rec_offset(
+            &config.rec,
+            recorded.before_offset.keys_len,
+            &counter,
+            kinds,
+        )
+        .context("failed to save record offset")?;
+    }
+
+    // The results of the run override those loaded from the record.
+    let coverage = src_names.map(|names| {
+        let statuses: HashMap<_, _> = rec
+            .outcomes
+            .iter()
+            .map(|(name, status)| (lookup_key(name, ignore_case), status_kind(status)))
+            .collect();
+        let good = names
+            .iter()
+            .filter(|name| {
+                let status = statuses.get(&name[..]).copied();
+                status.or_else(|| recorded.get(name)) == Some("good")
+            })
+            .count();
+        (good, names.len())
+    });
+
+    let dups = dups.map(|dups| {
+        let mut groups: Vec<Vec<String>> = dups
+            .into_values()
+            .filter(|group| group.len() > 1)
+            .map(|group| group.into_iter().cloned().collect())
+            .collect();
+        groups.sort_unstable();
+        groups
+    });
+    let reclaimed = match &dups {
+        Some(groups) if config.hardlink_duplicates => {
+            let statuses: HashMap<&str, &str> = rec
+                .outcomes
+                .iter()
+                .map(|(name, status)| (&name[..], status_kind(status)))
+                .collect();
+            let status = |path: &str| {
+                let name = file_name(Path::new(path), normalize);
+                statuses.get(&name[..]).copied()
+            };
+            Some(hardlink_dups(groups, status, dry_run, &mut out)?)
+        }
+        _ => None,
+    };
+    Ok(RunReport {
+        run_id,
+        counter,
+        outcomes: rec.outcomes,
+        recorded_at: rec.times,
+        transfers,
+        bytes: downloaded,
+        elapsed: started.elapsed(),
+        hosts: by_host,
+        dups,
+        reclaimed,
+        error_limit_reached,
+        coverage,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn names_from_urls() {
+        let naming = Naming::default();
+        let keep_query = Naming {
+            keep_query: true,
+            ..naming
+        };
+        let strip_fragment = Naming {
+            strip_fragment: true,
+            ..naming
+        };
+        let cases = [
+            ("http://h/a/x.zip", naming, "x.zip"),
+            ("http://h/x.zip?v=2", naming, "x.zip"),
+            // The last slash is taken even in the query, as names have always been derived.
+            ("http://h/dl?f=/x.zip", naming, "x.zip"),
+            ("http://h/x.zip#part", naming, "x.zip#part"),
+            ("http://h/x.zip?v=2", keep_query, "x.zip?v=2"),
+            ("http://h/dl?f=/x.zip", keep_query, "dl?f=/x.zip"),
+            ("http://h/x.zip#part", strip_fragment, "x.zip"),
+            ("http://h/dl?f=/x.zip#part", strip_fragment, "x.zip"),
+        ];
+        for (url, naming, name) in cases {
+            assert_eq!(name_from_url(url, naming), name, "{url}");
+        }
+    }
+
+    #[test]
+    fn error_kinds() {
+        let cases = [
+            (6, "dns"),              // COULDNT_RESOLVE_HOST
+            (5, "dns"),              // COULDNT_RESOLVE_PROXY
+            (28, "connect-timeout"), // OPERATION_TIMEDOUT, before the transfer began
+            (7, "connect"),          // COULDNT_CONNECT
+            (35, "tls"),             // SSL_CONNECT_ERROR
+            (60, "tls"),             // PEER_FAILED_VERIFICATION
+            (58, "tls"),             // SSL_CERTPROBLEM
+            (22, "http"),            // HTTP_RETURNED_ERROR
+            (52, "http"),            // GOT_NOTHING
+            (92, "http"),            // HTTP2_STREAM
+            (56, "transfer"),        // RECV_ERROR
+            (55, "transfer"),        // SEND_ERROR
+            (18, "transfer"),        // PARTIAL_FILE
+            (3, "other"),            // URL_MALFORMAT
+        ];
+        let mut handle = Easy::new();
+        for (code, kind) in cases {
+            let e = curl::Error::new(code);
+            assert_eq!(error_kind(&mut handle, &e), kind, "{e}");
+        }
+    }
+
+    #[test]
+    fn read_timeout_after_connecting() {
+        // Accepts the connection but never responds.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let _stream = listener.accept().unwrap();
+            thread::sleep(Duration::from_secs(5));
+        });
+
+        let mut handle = Easy::new();
+        handle.url(&format!("http://{addr}/")).unwrap();
+        handle.timeout(Duration::from_millis(300)).unwrap();
+        let e = handle.perform().unwrap_err();
+        assert_eq!(error_kind(&mut handle, &e), "read-timeout");
+    }
+}
@@ -0,0 +1,109 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    mem,
+    path::{Path, PathBuf},
+    ptr,
+    sync::Mutex,
+};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::{status_kind, timestamp};
+
+/// Writes timestamped log entries to a file, if one is open.
+struct FileLogger {
+    file: Mutex<Option<File>>,
+}
+
+/// Installed once per process, with its file swapped by each run that logs.
+static LOGGER: FileLogger = FileLogger {
+    file: Mutex::new(None),
+};
+
+impl Log for FileLogger {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let mut file = self.file.lock().unwrap();
+        let Some(file) = file.as_mut() else {
+            return;
+        };
+        let _ = writeln!(
+            file,
+            "{} {:5} {}",
+            timestamp(),
+            record.level(),
+            record.args()
+        );
+    }
+
+    fn flush(&self) {
+        if let Some(file) = self.file.lock().unwrap().as_mut() {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Makes the logger write entries up to `level` to `path`, appending to it
+/// or, with `rotate`, first renaming an existing log to `<path>.1`.
+///
+/// The logger is installed on the first call, and later calls only swap its file,
+/// failing if another logger was installed in the meantime.
+pub fn init(path: &Path, level: LevelFilter, rotate: bool) -> io::Result<()> {
+    if log::set_logger(&LOGGER).is_err() && !ptr::addr_eq(log::logger(), &LOGGER) {
+        return Err(io::Error::other("another logger is installed"));
+    }
+    if rotate && path.exists() {
+        let mut old = PathBuf::from(path).into_os_string();
+        old.push(".1");
+        fs::rename(path, old)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    *LOGGER.file.lock().unwrap() = Some(file);
+    log::set_max_level(level);
+    Ok(())
+}
+
+/// Passes record lines through to a writer, logging each of them
+/// with a level according to its status.
+pub struct Logged<W> {
+    inner: W,
+    line: Vec<u8>,
+}
+
+impl<W> Logged<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            line: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> Write for Logged<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        for &b in &buf[..n] {
+            if b != b'\n' {
+                self.line.push(b);
+                continue;
+            }
+            let line = String::from_utf8_lossy(&mem::take(&mut self.line)).into_owned();
+            let status = line.split_once(": ").map_or("", |(_, status)| status);
+            let level = match status_kind(status) {
+                "bad" => Level::Warn,
+                "error" => Level::Error,
+                _ => Level::Info,
+            };
+            log::log!(level, "{line}");
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
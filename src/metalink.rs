@@ -0,0 +1,69 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::{bail, Context, Result};
+use roxmltree::{Document, Node};
+
+use crate::cas::Algo;
+
+const NS: &str = "urn:ietf:params:xml:ns:metalink";
+
+/// A file described by a Metalink.
+pub struct Entry {
+    /// The URLs of the mirrors, from the highest priority. There is at least one.
+    pub urls: Vec<String>,
+    /// The longest digest given with a supported algorithm.
+    pub digest: Option<(Algo, String)>,
+}
+
+fn children<'a, 'input>(
+    node: Node<'a, 'input>,
+    tag: &'static str,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children()
+        .filter(move |child| child.has_tag_name((NS, tag)))
+}
+
+/// Returns whether a source is a Metalink 4 file, by its extension.
+pub fn is_metalink(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "meta4") && path.is_file()
+}
+
+/// Loads the files of a Metalink 4 document (RFC 5854), keyed by name.
+///
+/// Names are reduced to their last path segment, as local files are matched by file name.
+pub fn load(path: &Path) -> Result<HashMap<String, Entry>> {
+    let xml = fs::read_to_string(path)?;
+    let doc = Document::parse(&xml).context("invalid XML")?;
+    let root = doc.root_element();
+    if !root.has_tag_name((NS, "metalink")) {
+        bail!("not a Metalink 4 document");
+    }
+
+    let mut files = HashMap::new();
+    for file in children(root, "file") {
+        let name = file.attribute("name").context("file without a name")?;
+        let name = name.rsplit('/').next().unwrap();
+        // Priority 1 is the highest, and URLs without one come last.
+        let mut urls: Vec<_> = children(file, "url")
+            .filter_map(|url| {
+                let priority = url.attribute("priority").and_then(|p| p.parse().ok());
+                Some((priority.unwrap_or(u32::MAX), url.text()?.trim().to_owned()))
+            })
+            .collect();
+        if urls.is_empty() {
+            bail!("no URL for `{name}`");
+        }
+        urls.sort_by_key(|&(priority, _)| priority);
+        let urls = urls.into_iter().map(|(_, url)| url).collect();
+        let digest = children(file, "hash")
+            .filter_map(|hash| {
+                // Hash types are named like `sha-256`.
+                let algo: Algo = hash.attribute("type")?.replace('-', "").parse().ok()?;
+                let hex = hash.text()?.trim();
+                (hex.len() == algo.hex_len()).then(|| (algo, hex.to_ascii_lowercase()))
+            })
+            .max_by_key(|(algo, _)| algo.hex_len());
+        files.insert(name.into(), Entry { urls, digest });
+    }
+    Ok(files)
+}
@@ -0,0 +1,130 @@
+use std::{cmp::Ordering, collections::BTreeMap, fs, io, path::PathBuf, time::Duration};
+
+use crate::write_atomic;
+
+/// Returns the scheme, host and port of a URL, which are taken to be a mirror.
+fn mirror(url: &str) -> Option<String> {
+    let (scheme, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next().unwrap();
+    let host_port = authority.rsplit_once('@').map_or(authority, |(_, hp)| hp);
+    (!host_port.is_empty()).then(|| format!("{scheme}://{host_port}"))
+}
+
+/// Transfers from a mirror in past runs.
+#[derive(Default)]
+struct Stats {
+    successes: u32,
+    failures: u32,
+    bytes: u64,
+    secs: f64,
+}
+
+impl Stats {
+    /// Returns the success rate and the average speed in bytes per second.
+    fn rank(&self) -> (f64, f64) {
+        let rate = self.successes as f64 / (self.successes + self.failures) as f64;
+        let speed = if self.secs > 0.0 {
+            self.bytes as f64 / self.secs
+        } else {
+            0.0
+        };
+        (rate, speed)
+    }
+}
+
+/// Success rates and speeds of mirrors, kept in a file across runs
+/// to try the historically best mirror of a file first.
+pub struct MirrorRank {
+    path: PathBuf,
+    mirrors: BTreeMap<String, Stats>,
+}
+
+fn parse_line(line: &str) -> Option<(String, Stats)> {
+    let mut fields = line.split(' ');
+    let mirror = fields.next()?.to_string();
+    let stats = Stats {
+        successes: fields.next()?.parse().ok()?,
+        failures: fields.next()?.parse().ok()?,
+        bytes: fields.next()?.parse().ok()?,
+        secs: fields.next()?.parse().ok()?,
+    };
+    fields.next().is_none().then_some((mirror, stats))
+}
+
+impl MirrorRank {
+    /// Loads the stats saved at `path`, if any, or starts afresh if `reset` is set.
+    pub fn load(path: PathBuf, reset: bool) -> io::Result<Self> {
+        let mut mirrors = BTreeMap::new();
+        let content = match fs::read_to_string(&path) {
+            Ok(_) if reset => String::new(),
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        for (i, line) in content.lines().enumerate() {
+            let (mirror, stats) = parse_line(line).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid mirror stats on line {}", i + 1),
+                )
+            })?;
+            mirrors.insert(mirror, stats);
+        }
+        Ok(Self { path, mirrors })
+    }
+
+    fn rank(&self, url: &str) -> Option<(f64, f64)> {
+        mirror(url)
+            .and_then(|mirror| self.mirrors.get(&mirror))
+            .map(Stats::rank)
+    }
+
+    /// Sorts URLs by the success rate of their mirrors, then by their speed.
+    ///
+    /// URLs of mirrors never tried stay in front in their original order, so that they get measured.
+    pub fn sort(&self, urls: &mut [String]) {
+        urls.sort_by(|a, b| match (self.rank(a), self.rank(b)) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (Some((rate_a, speed_a)), Some((rate_b, speed_b))) => {
+                rate_b.total_cmp(&rate_a).then(speed_b.total_cmp(&speed_a))
+            }
+        });
+    }
+
+    fn stats(&mut self, url: &str) -> Option<&mut Stats> {
+        mirror(url).map(|mirror| self.mirrors.entry(mirror).or_default())
+    }
+
+    /// Counts a complete transfer of `bytes` from `url`.
+    pub fn succeeded(&mut self, url: &str, bytes: u64, elapsed: Duration) {
+        if let Some(stats) = self.stats(url) {
+            stats.successes += 1;
+            stats.bytes += bytes;
+            stats.secs += elapsed.as_secs_f64();
+        }
+    }
+
+    /// Counts a failed transfer from `url`.
+    pub fn failed(&mut self, url: &str) {
+        if let Some(stats) = self.stats(url) {
+            stats.failures += 1;
+        }
+    }
+
+    /// Saves the stats, one `MIRROR SUCCESSES FAILURES BYTES SECONDS` line for each mirror.
+    pub fn save(&self) -> io::Result<()> {
+        let mut content = String::new();
+        for (mirror, stats) in &self.mirrors {
+            let Stats {
+                successes,
+                failures,
+                bytes,
+                secs,
+            } = stats;
+            content += &format!("{mirror} {successes} {failures} {bytes} {secs:.3}\n");
+        }
+        write_atomic(&self.path, content)
+    }
+}
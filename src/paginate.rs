@@ -0,0 +1,63 @@
+use curl::easy::Easy;
+
+use crate::{compare::Comparator, redirect::RedirectGuard};
+
+/// A page that failed to be fetched, ending a paginated resource midway.
+pub struct PageError {
+    pub page: u64,
+    pub reason: String,
+}
+
+/// Fetches a resource served in pages from `url`, whose `{page}` placeholder is replaced
+/// with consecutive numbers from `first`, feeding the page bodies to `cmp` in order.
+///
+/// The resource ends at the first empty page, or at a 404 after the first page.
+/// A URL without the placeholder is fetched as the only page.
+/// Returns the number of pages with content.
+pub fn fetch(
+    handle: &mut Easy,
+    url: &str,
+    first: u64,
+    sign: impl Fn(String) -> String,
+    guard: &mut Option<RedirectGuard>,
+    cmp: &mut Comparator,
+) -> Result<u64, PageError> {
+    let mut page = first;
+    loop {
+        handle
+            .url(&sign(url.replace("{page}", &page.to_string())))
+            .unwrap();
+        let received = cmp.received;
+        let mut transfer = handle.transfer();
+        transfer
+            .write_function(|data| {
+                cmp.update(data);
+                Ok(data.len())
+            })
+            .unwrap();
+        transfer
+            .header_function(|header| guard.as_mut().is_none_or(|g| g.check(header)))
+            .unwrap();
+        let res = transfer.perform();
+        drop(transfer);
+
+        let blocked = guard.as_ref().and_then(RedirectGuard::take_blocked);
+        let code = handle.response_code().unwrap();
+        let reason = match (res, blocked) {
+            (Err(_), Some(location)) => format!("redirected to {location}"),
+            (Err(e), None) => e.to_string(),
+            (Ok(()), _) if code == 404 && page != first => break,
+            (Ok(()), _) if !(200..300).contains(&code) => format!("response code {code}"),
+            (Ok(()), _) if cmp.received == received => break,
+            (Ok(()), _) => {
+                page += 1;
+                if !url.contains("{page}") {
+                    break;
+                }
+                continue;
+            }
+        };
+        return Err(PageError { page, reason });
+    }
+    Ok(page - first)
+}
@@ -0,0 +1,43 @@
+use std::path::Path;
+
+use libloading::Library;
+
+/// Signature of the `howis_compare` hook exported by a plugin:
+///
+/// ```c
+/// int64_t howis_compare(const uint8_t *local, const uint8_t *remote, size_t len, uint64_t offset);
+/// ```
+///
+/// It is given `len` bytes of the local and remote files starting at `offset`,
+/// and returns a negative value if they are to be considered equal, or else
+/// the index of the first byte considered different.
+type CompareFn = unsafe extern "C" fn(*const u8, *const u8, usize, u64) -> i64;
+
+/// A dynamic library providing its own equality of file contents.
+pub struct Plugin {
+    compare: CompareFn,
+    // Declared last so that the library is unloaded after the hook is gone.
+    _lib: Library,
+}
+
+impl Plugin {
+    pub fn load(path: &Path) -> Result<Self, libloading::Error> {
+        // SAFETY: the library is trusted by the user to be a howis plugin.
+        unsafe {
+            let lib = Library::new(path)?;
+            let compare = *lib.get::<CompareFn>(b"howis_compare\0")?;
+            Ok(Self { compare, _lib: lib })
+        }
+    }
+
+    /// Returns the index of the first byte considered different, if any.
+    pub fn compare(&self, local: &[u8], remote: &[u8], offset: u64) -> Option<usize> {
+        let len = local.len().min(remote.len());
+        if len == 0 {
+            return None;
+        }
+        // SAFETY: both pointers are valid for `len` bytes.
+        let res = unsafe { (self.compare)(local.as_ptr(), remote.as_ptr(), len, offset) };
+        usize::try_from(res).ok().map(|i| i.min(len - 1))
+    }
+}
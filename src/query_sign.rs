@@ -0,0 +1,30 @@
+use crate::s3::{hex, hmac};
+
+/// Signs URLs with an HMAC-SHA256 of their path and query, appended as a query parameter.
+pub struct QuerySigner {
+    secret: String,
+    param: String,
+}
+
+impl QuerySigner {
+    pub fn new(secret: String, param: String) -> Self {
+        Self { secret, param }
+    }
+
+    /// Returns the URL with the signature of everything after its authority appended.
+    pub fn sign(&self, url: &str) -> String {
+        let (_, rest) = url.split_once("://").unwrap_or(("", url));
+        let (url, fragment) = match url.split_once('#') {
+            Some((url, fragment)) => (url, Some(fragment)),
+            None => (url, None),
+        };
+        let rest = rest.split('#').next().unwrap();
+        let request = rest.find('/').map_or("/", |i| &rest[i..]);
+        let signature = hex(&hmac(self.secret.as_bytes(), request));
+        let sep = if url.contains('?') { '&' } else { '?' };
+        match fragment {
+            Some(fragment) => format!("{url}{sep}{}={signature}#{fragment}", self.param),
+            None => format!("{url}{sep}{}={signature}", self.param),
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::sync::{Arc, Mutex};
+
+use crate::url_host;
+
+/// Refuses to follow redirects to hosts outside an allowlist.
+///
+/// Response headers are checked as they arrive, so that a transfer is aborted
+/// on a redirect before any request is made to the host it points to.
+#[derive(Clone)]
+pub struct RedirectGuard {
+    allow: Arc<[String]>,
+    /// The location of the last redirect blocked.
+    blocked: Arc<Mutex<Option<String>>>,
+    /// Whether the response being received is a redirect.
+    redirecting: bool,
+}
+
+impl RedirectGuard {
+    pub fn new(allow: &[String]) -> Self {
+        Self {
+            allow: allow.into(),
+            blocked: Arc::default(),
+            redirecting: false,
+        }
+    }
+
+    /// Checks a response header, returning `false` to abort the transfer
+    /// if it redirects to a host that isn't allowed.
+    pub fn check(&mut self, header: &[u8]) -> bool {
+        let header = String::from_utf8_lossy(header);
+        if header.starts_with("HTTP/") {
+            let code = header.split(' ').nth(1).and_then(|code| code.parse().ok());
+            self.redirecting = code.is_some_and(|code: u32| (300..400).contains(&code));
+            return true;
+        }
+        let Some((key, value)) = header.split_once(':') else {
+            return true;
+        };
+        if !self.redirecting || !key.eq_ignore_ascii_case("location") {
+            return true;
+        }
+        let location = value.trim();
+        // Relative redirects stay on the same host.
+        let host = match location.strip_prefix("//") {
+            Some(rest) => url_host(&format!("http://{rest}")).map(str::to_owned),
+            None if location.contains("://") => url_host(location).map(str::to_owned),
+            None => return true,
+        };
+        let allowed = host.is_some_and(|host| {
+            self.allow
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(&host))
+        });
+        if !allowed {
+            *self.blocked.lock().unwrap() = Some(location.into());
+        }
+        allowed
+    }
+
+    /// Returns the location of a redirect blocked since the last call, if any.
+    pub fn take_blocked(&self) -> Option<String> {
+        self.blocked.lock().unwrap().take()
+    }
+}
@@ -0,0 +1,161 @@
+use std::{collections::HashMap, env, fs, path::PathBuf};
+
+use chrono::Utc;
+use curl::easy::List;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+/// SHA-256 of an empty payload, which is all we ever send.
+const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+/// Signs requests to `s3://bucket/key` URLs with AWS Signature Version 4.
+pub struct Signer {
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+    region: String,
+}
+
+impl Signer {
+    /// Loads credentials and region from the environment, falling back to
+    /// `~/.aws/credentials` and `~/.aws/config` for the selected profile.
+    pub fn from_env() -> Option<Self> {
+        let profile = env::var("AWS_PROFILE").unwrap_or_else(|_| "default".into());
+        let aws_dir = env::var_os("HOME")
+            .or_else(|| env::var_os("USERPROFILE"))
+            .map(|home| PathBuf::from(home).join(".aws"));
+        let load = |file: &str, section: &str| {
+            aws_dir
+                .as_ref()
+                .and_then(|dir| fs::read_to_string(dir.join(file)).ok())
+                .map(|s| parse_ini_section(&s, section))
+                .unwrap_or_default()
+        };
+
+        let (access_key, secret_key, session_token) = match (
+            env::var("AWS_ACCESS_KEY_ID"),
+            env::var("AWS_SECRET_ACCESS_KEY"),
+        ) {
+            (Ok(access_key), Ok(secret_key)) => {
+                (access_key, secret_key, env::var("AWS_SESSION_TOKEN").ok())
+            }
+            _ => {
+                let mut creds = load("credentials", &profile);
+                (
+                    creds.remove("aws_access_key_id")?,
+                    creds.remove("aws_secret_access_key")?,
+                    creds.remove("aws_session_token"),
+                )
+            }
+        };
+
+        let config_section = if profile == "default" {
+            profile
+        } else {
+            format!("profile {profile}")
+        };
+        let region = env::var("AWS_REGION")
+            .or_else(|_| env::var("AWS_DEFAULT_REGION"))
+            .ok()
+            .or_else(|| load("config", &config_section).remove("region"))
+            .unwrap_or_else(|| "us-east-1".into());
+
+        Some(Self {
+            access_key,
+            secret_key,
+            session_token,
+            region,
+        })
+    }
+
+    /// Translates an `s3://` URL to its HTTPS endpoint, appending the signature
+    /// headers for `method` to `headers`. Other URLs are returned unchanged.
+    pub fn sign(&self, method: &str, url: &str, headers: &mut List) -> String {
+        let Some(rest) = url.strip_prefix("s3://") else {
+            return url.into();
+        };
+        let (bucket, key) = rest.split_once('/').unwrap_or((rest, ""));
+        let host = format!("{bucket}.s3.{}.amazonaws.com", self.region);
+        let path = format!("/{}", uri_encode(key));
+
+        let now = Utc::now();
+        let date = now.format("%Y%m%d").to_string();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+
+        let mut canonical_headers =
+            format!("host:{host}\nx-amz-content-sha256:{EMPTY_SHA256}\nx-amz-date:{amz_date}\n");
+        let mut signed_headers = "host;x-amz-content-sha256;x-amz-date".to_string();
+        if let Some(token) = &self.session_token {
+            canonical_headers += &format!("x-amz-security-token:{token}\n");
+            signed_headers += ";x-amz-security-token";
+        }
+        let canonical_request =
+            format!("{method}\n{path}\n\n{canonical_headers}\n{signed_headers}\n{EMPTY_SHA256}");
+
+        let scope = format!("{date}/{}/s3/aws4_request", self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+            hex(&Sha256::digest(canonical_request))
+        );
+        let mut key = hmac(format!("AWS4{}", self.secret_key).as_bytes(), &date);
+        for part in [&self.region[..], "s3", "aws4_request"] {
+            key = hmac(&key, part);
+        }
+        let signature = hex(&hmac(&key, &string_to_sign));
+
+        headers
+            .append(&format!(
+                "Authorization: AWS4-HMAC-SHA256 Credential={}/{scope}, \
+                 SignedHeaders={signed_headers}, Signature={signature}",
+                self.access_key
+            ))
+            .unwrap();
+        headers
+            .append(&format!("x-amz-content-sha256: {EMPTY_SHA256}"))
+            .unwrap();
+        headers.append(&format!("x-amz-date: {amz_date}")).unwrap();
+        if let Some(token) = &self.session_token {
+            headers
+                .append(&format!("x-amz-security-token: {token}"))
+                .unwrap();
+        }
+
+        format!("https://{host}{path}")
+    }
+}
+
+fn parse_ini_section(s: &str, section: &str) -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    let mut in_section = false;
+    for line in s.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            in_section = name.trim() == section;
+        } else if let (true, Some((key, value))) = (in_section, line.split_once('=')) {
+            map.insert(key.trim().into(), value.trim().into());
+        }
+    }
+    map
+}
+
+pub fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Percent-encodes everything but unreserved characters and `/`.
+fn uri_encode(s: &str) -> String {
+    let mut res = String::new();
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-_.~/".contains(&b) {
+            res.push(b as char);
+        } else {
+            res += &format!("%{b:02X}");
+        }
+    }
+    res
+}
@@ -0,0 +1,66 @@
+use std::str::FromStr;
+
+use chrono::{Local, Timelike};
+
+use crate::parse_size;
+
+/// Receive speed limits by time of day, given as `HH:MM-HH:MM=RATE,...`.
+///
+/// A rate is a number of bytes per second with an optional `K`, `M` or `G`
+/// suffix, or `unlimited`. A range may wrap around midnight, and the first
+/// range containing the current time applies.
+#[derive(Clone)]
+pub struct Schedule(Vec<(u32, u32, u64)>);
+
+fn parse_time(s: &str) -> Option<u32> {
+    let (h, m) = s.split_once(':')?;
+    let (h, m): (u32, u32) = (h.parse().ok()?, m.parse().ok()?);
+    (h <= 24 && m < 60 && h * 60 + m <= 24 * 60).then_some(h * 60 + m)
+}
+
+fn parse_rate(s: &str) -> Option<u64> {
+    if s == "unlimited" {
+        return Some(0);
+    }
+    parse_size(s).filter(|&rate| rate != 0)
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        s.split(',')
+            .map(|rule| {
+                let (range, rate) = rule
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected HH:MM-HH:MM=RATE in `{rule}`"))?;
+                let (start, end) = range
+                    .split_once('-')
+                    .and_then(|(start, end)| Some((parse_time(start)?, parse_time(end)?)))
+                    .ok_or_else(|| format!("invalid time range `{range}`"))?;
+                let rate = parse_rate(rate).ok_or_else(|| format!("invalid rate `{rate}`"))?;
+                Ok((start, end, rate))
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+impl Schedule {
+    /// Returns the limit in bytes per second for the current local time,
+    /// or 0 if there is none.
+    pub fn current_limit(&self) -> u64 {
+        let now = Local::now();
+        let t = now.hour() * 60 + now.minute();
+        self.0
+            .iter()
+            .find(|&&(start, end, _)| {
+                if start <= end {
+                    (start..end).contains(&t)
+                } else {
+                    t >= start || t < end
+                }
+            })
+            .map_or(0, |&(_, _, rate)| rate)
+    }
+}
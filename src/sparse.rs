@@ -0,0 +1,50 @@
+use std::{
+    fs::File,
+    io,
+    os::unix::{
+        fs::{FileExt, MetadataExt},
+        io::AsRawFd,
+    },
+};
+
+use libc::{c_int, off_t, SEEK_DATA, SEEK_HOLE};
+
+fn seek(file: &File, offset: u64, whence: c_int) -> io::Result<Option<u64>> {
+    let res = unsafe { libc::lseek(file.as_raw_fd(), offset as off_t, whence) };
+    if res >= 0 {
+        return Ok(Some(res as u64));
+    }
+    let err = io::Error::last_os_error();
+    // There is no data past the offset.
+    if err.raw_os_error() == Some(libc::ENXIO) {
+        Ok(None)
+    } else {
+        Err(err)
+    }
+}
+
+/// Returns the offset of the first block of a file that is allocated but holds
+/// only zeros, where a sparse file would have a hole.
+///
+/// On file systems without hole support, every block counts as allocated.
+pub fn first_filled_hole(file: &File) -> io::Result<Option<u64>> {
+    let meta = file.metadata()?;
+    let block = meta.blksize().max(512);
+    let mut buf = vec![0; block as usize];
+    let mut pos = 0;
+    while let Some(data) = seek(file, pos, SEEK_DATA)? {
+        let hole = seek(file, data, SEEK_HOLE)?.unwrap_or(meta.len());
+        // Only whole blocks can be holes, and the last one may be partial.
+        let mut offset = data.next_multiple_of(block);
+        while offset < hole {
+            let len = block.min(meta.len() - offset) as usize;
+            file.read_exact_at(&mut buf[..len], offset)?;
+            if len == block as usize && buf.iter().all(|&b| b == 0) {
+                return Ok(Some(offset));
+            }
+            offset += block;
+        }
+        pos = hole;
+    }
+    Ok(None)
+}
@@ -0,0 +1,136 @@
+use std::{path::Path, time::SystemTime};
+
+use rusqlite::{params, Connection};
+
+/// A result recorded in a run.
+pub struct Row<'a> {
+    pub name: &'a str,
+    pub status: &'a str,
+    /// Bytes received for the file, if it was downloaded.
+    pub bytes: Option<u64>,
+    /// Average download speed in bytes per second.
+    pub speed: Option<f64>,
+    pub recorded_at: SystemTime,
+}
+
+/// Appends the results of a run to a SQLite database, creating its table if absent.
+///
+/// Times are stored in UTC in the format of SQLite's `datetime()`, so that they can be
+/// compared with e.g. `datetime('now', '-1 month')`.
+pub fn export<'a>(
+    path: &Path,
+    run: &str,
+    rows: impl Iterator<Item = Row<'a>>,
+) -> rusqlite::Result<()> {
+    let mut conn = Connection::open(path)?;
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS results (
+            run TEXT NOT NULL,
+            name TEXT NOT NULL,
+            status TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            bytes INTEGER,
+            speed REAL,
+            recorded_at TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS results_name ON results (name, recorded_at);",
+    )?;
+    let tx = conn.transaction()?;
+    {
+        let mut insert = tx.prepare(
+            "INSERT INTO results (run, name, status, kind, bytes, speed, recorded_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )?;
+        for row in rows {
+            insert.execute(params![
+                run,
+                row.name,
+                row.status,
+                crate::status_kind(row.status),
+                row.bytes.map(|bytes| bytes as i64),
+                row.speed,
+                crate::utc_datetime(row.recorded_at),
+            ])?;
+        }
+    }
+    tx.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn rows_round_trip() {
+        let path = std::env::temp_dir().join(format!("howis-sqlite-{}.db", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(951_827_696);
+        let rows = [
+            Row {
+                name: "a.txt",
+                status: "good",
+                bytes: Some(42),
+                speed: Some(21.0),
+                recorded_at: time,
+            },
+            Row {
+                name: "b.txt",
+                status: "bad: too short",
+                bytes: None,
+                speed: None,
+                recorded_at: time,
+            },
+        ];
+        export(&path, "run", rows.into_iter()).unwrap();
+
+        let conn = Connection::open(&path).unwrap();
+        let mut select = conn
+            .prepare("SELECT run, name, status, kind, bytes, speed, recorded_at FROM results")
+            .unwrap();
+        let read: Vec<_> = select
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, Option<i64>>(4)?,
+                    row.get::<_, Option<f64>>(5)?,
+                    row.get::<_, String>(6)?,
+                ))
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        drop(select);
+        drop(conn);
+        fs::remove_file(&path).unwrap();
+
+        let at = "2000-02-29 12:34:56".to_string();
+        assert_eq!(
+            read,
+            [
+                (
+                    "run".into(),
+                    "a.txt".into(),
+                    "good".into(),
+                    "good".into(),
+                    Some(42),
+                    Some(21.0),
+                    at.clone(),
+                ),
+                (
+                    "run".into(),
+                    "b.txt".into(),
+                    "bad: too short".into(),
+                    "bad".into(),
+                    None,
+                    None,
+                    at,
+                ),
+            ]
+        );
+    }
+}
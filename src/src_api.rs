@@ -0,0 +1,106 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::{bail, Context, Result};
+use curl::easy::Easy;
+use serde_json::{json, Value};
+
+use crate::{name_from_url, write_atomic, Naming};
+
+/// Fetches the body of a JSON inventory document.
+pub fn fetch(url: &str, user: Option<&String>, pass: Option<&String>) -> Result<Vec<u8>> {
+    let mut handle = Easy::new();
+    handle.url(url)?;
+    handle.follow_location(true)?;
+    handle.fail_on_error(true)?;
+    if let Some(user) = user {
+        handle.username(user)?;
+    }
+    if let Some(pass) = pass {
+        handle.password(pass)?;
+    }
+
+    let mut body = Vec::new();
+    let mut transfer = handle.transfer();
+    transfer.write_function(|data| {
+        body.extend_from_slice(data);
+        Ok(data.len())
+    })?;
+    transfer.perform()?;
+    drop(transfer);
+    Ok(body)
+}
+
+/// Saves a response to `cache` along with the URL and the path it was fetched for.
+pub fn save_cache(cache: &Path, url: &str, path: &str, body: &[u8]) -> Result<()> {
+    let response: Value = serde_json::from_slice(body).context("invalid JSON")?;
+    let saved = json!({ "url": url, "path": path, "response": response });
+    Ok(write_atomic(cache, format!("{saved}\n"))?)
+}
+
+/// Loads the response saved to `cache`, refusing one fetched for another URL or path,
+/// which would silently check files against another inventory.
+pub fn load_cache(cache: &Path, url: &str, path: &str) -> Result<Vec<u8>> {
+    let saved: Value = serde_json::from_slice(&fs::read(cache)?).context("invalid JSON")?;
+    let (Some(saved_url), Some(saved_path), Some(response)) = (
+        saved.get("url").and_then(Value::as_str),
+        saved.get("path").and_then(Value::as_str),
+        saved.get("response"),
+    ) else {
+        bail!(
+            "{} is not a source API cache, delete it to fetch the list again",
+            cache.display()
+        );
+    };
+    if saved_url != url || saved_path != path {
+        bail!(
+            "{} was fetched from {saved_url} at `{saved_path}`, delete it to fetch the list again",
+            cache.display()
+        );
+    }
+    Ok(serde_json::to_vec(response)?)
+}
+
+/// Extracts name-to-URL pairs from the value at a dot-separated `path` in a JSON document.
+///
+/// The value may be an object mapping names to URLs, an array of URLs,
+/// or an array of objects with a `url` and an optional `name` field.
+/// Names are derived from URLs with `naming` as for a URL list if not given.
+pub fn parse(body: &[u8], path: &str, naming: Naming) -> Result<HashMap<String, String>> {
+    let mut value: &Value = &serde_json::from_slice(body).context("invalid JSON")?;
+    for key in path.split('.').filter(|key| !key.is_empty()) {
+        value = match value {
+            Value::Object(map) => map.get(key),
+            Value::Array(vec) => key.parse().ok().and_then(|i: usize| vec.get(i)),
+            _ => None,
+        }
+        .with_context(|| format!("no value at `{path}`"))?;
+    }
+
+    let mut map = HashMap::new();
+    match value {
+        Value::Object(obj) => {
+            for (name, url) in obj {
+                let url = url
+                    .as_str()
+                    .with_context(|| format!("non-string URL for {name}"))?;
+                map.insert(name.clone(), url.into());
+            }
+        }
+        Value::Array(vec) => {
+            for entry in vec {
+                let (name, url) = match entry {
+                    Value::String(url) => (None, url),
+                    Value::Object(obj) => match obj.get("url") {
+                        Some(Value::String(url)) => (obj.get("name").and_then(Value::as_str), url),
+                        _ => bail!("entry without a string `url` field"),
+                    },
+                    _ => bail!("entry is neither a URL nor an object"),
+                };
+                let name = name.unwrap_or_else(|| name_from_url(url, naming));
+                map.insert(name.into(), url.clone());
+            }
+        }
+        _ => bail!("value at `{path}` is neither an object nor an array"),
+    }
+    Ok(map)
+}
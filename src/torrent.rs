@@ -0,0 +1,319 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File},
+    io::{self, Read},
+    mem,
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+use sha1::{Digest, Sha1};
+
+/// A bencoded value.
+enum Value<'a> {
+    Int(i64),
+    Bytes(&'a [u8]),
+    List(Vec<Value<'a>>),
+    Dict(BTreeMap<&'a [u8], Value<'a>>),
+}
+
+/// Deepest nesting of lists and dictionaries decoded, far beyond that of any torrent,
+/// so that a malformed file can't overflow the stack.
+const MAX_DEPTH: usize = 32;
+
+fn decode<'a>(s: &mut &'a [u8], depth: usize) -> Result<Value<'a>> {
+    fn take_until<'a>(s: &mut &'a [u8], end: u8) -> Result<&'a [u8]> {
+        let i = s.iter().position(|&b| b == end).context("unexpected end")?;
+        let res = &s[..i];
+        *s = &s[i + 1..];
+        Ok(res)
+    }
+    fn parse_int<T: std::str::FromStr>(s: &[u8]) -> Result<T> {
+        std::str::from_utf8(s)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .context("invalid integer")
+    }
+
+    Ok(match s.first().context("unexpected end")? {
+        b'i' => {
+            *s = &s[1..];
+            Value::Int(parse_int(take_until(s, b'e')?)?)
+        }
+        b'l' | b'd' => {
+            if depth == MAX_DEPTH {
+                bail!("nested too deeply");
+            }
+            let is_dict = s[0] == b'd';
+            *s = &s[1..];
+            let mut items = Vec::new();
+            while s.first() != Some(&b'e') {
+                items.push(decode(s, depth + 1)?);
+            }
+            *s = &s[1..];
+            if !is_dict {
+                return Ok(Value::List(items));
+            }
+            let mut dict = BTreeMap::new();
+            let mut items = items.into_iter();
+            while let Some(key) = items.next() {
+                let (Value::Bytes(key), Some(value)) = (key, items.next()) else {
+                    bail!("invalid dictionary");
+                };
+                dict.insert(key, value);
+            }
+            Value::Dict(dict)
+        }
+        _ => {
+            let len: usize = parse_int(take_until(s, b':')?)?;
+            if len > s.len() {
+                bail!("unexpected end");
+            }
+            let (bytes, rest) = s.split_at(len);
+            *s = rest;
+            Value::Bytes(bytes)
+        }
+    })
+}
+
+impl<'a> Value<'a> {
+    fn get(&self, key: &str) -> Result<&Value<'a>> {
+        match self {
+            Value::Dict(dict) => dict.get(key.as_bytes()),
+            _ => None,
+        }
+        .with_context(|| format!("missing `{key}`"))
+    }
+
+    fn int(&self) -> Result<u64> {
+        match self {
+            &Value::Int(i) if i >= 0 => Ok(i as u64),
+            _ => bail!("expected non-negative integer"),
+        }
+    }
+
+    fn bytes(&self) -> Result<&'a [u8]> {
+        match self {
+            Value::Bytes(bytes) => Ok(bytes),
+            _ => bail!("expected byte string"),
+        }
+    }
+
+    fn list(&self) -> Result<&[Value<'a>]> {
+        match self {
+            Value::List(list) => Ok(list),
+            _ => bail!("expected list"),
+        }
+    }
+}
+
+/// The result of verifying a file against the piece hashes of a torrent.
+pub enum Verdict {
+    Good,
+    /// The local file has a different length than the one in the torrent.
+    SizeMismatch,
+    /// Some pieces covering the file failed to verify.
+    Bad {
+        /// Indices of the failed pieces.
+        failed: Vec<usize>,
+        /// Number of pieces covering the file that were verified, failed or not.
+        verified: usize,
+    },
+    /// Number of pieces that could not be verified because they span
+    /// other files that are missing or have the wrong length.
+    Unverified(usize),
+}
+
+/// The piece layout of a torrent.
+pub struct Torrent {
+    piece_len: u64,
+    pieces: Vec<[u8; 20]>,
+    /// Names and lengths of the files in order.
+    files: Vec<(String, u64)>,
+}
+
+impl Torrent {
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read(path)?;
+        let mut s = &data[..];
+        let info = decode(&mut s, 0)?;
+        let info = info.get("info")?;
+
+        let piece_len = info.get("piece length")?.int()?;
+        if piece_len == 0 {
+            bail!("zero piece length");
+        }
+        let pieces = info.get("pieces")?.bytes()?;
+        if pieces.len() % 20 != 0 {
+            bail!("invalid piece hashes");
+        }
+        let pieces = pieces
+            .chunks(20)
+            .map(|hash| hash.try_into().unwrap())
+            .collect();
+
+        let name = |bytes| String::from_utf8_lossy(bytes).into_owned();
+        let files = match info.get("files") {
+            // Only the last path component is used, like with URLs.
+            Ok(files) => files
+                .list()?
+                .iter()
+                .map(|file| {
+                    let path = file.get("path")?.list()?;
+                    let last = path.last().context("empty path")?.bytes()?;
+                    Ok((name(last), file.get("length")?.int()?))
+                })
+                .collect::<Result<_>>()?,
+            Err(_) => vec![(name(info.get("name")?.bytes()?), info.get("length")?.int()?)],
+        };
+
+        let total = files
+            .iter()
+            .try_fold(0u64, |total, (_, len)| total.checked_add(*len))
+            .context("total length overflows")?;
+        let res = Self {
+            piece_len,
+            pieces,
+            files,
+        };
+        if total.div_ceil(piece_len) != res.pieces.len() as u64 {
+            bail!("piece count doesn't match total length");
+        }
+        Ok(res)
+    }
+
+    /// Verifies every file in the torrent that has a local copy in `locals`,
+    /// which maps file names to paths.
+    pub fn verify(&self, locals: &HashMap<&str, &Path>) -> io::Result<HashMap<String, Verdict>> {
+        let mut failed = vec![Vec::new(); self.files.len()];
+        let mut unverified = vec![0; self.files.len()];
+        let mut verified = vec![0; self.files.len()];
+        let mut readers = Vec::with_capacity(self.files.len());
+        let mut verdicts = HashMap::new();
+        for (name, len) in &self.files {
+            let Some(path) = locals.get(&name[..]) else {
+                readers.push(None);
+                continue;
+            };
+            let file = File::open(path)?;
+            if file.metadata()?.len() == *len {
+                readers.push(Some(file));
+            } else {
+                readers.push(None);
+                verdicts.insert(name.clone(), Verdict::SizeMismatch);
+            }
+        }
+
+        // Pieces run across file boundaries, so all files are read as one stream.
+        let mut piece = 0;
+        let mut hasher = Sha1::new();
+        let mut filled = 0;
+        let mut tainted = false;
+        let mut touched = Vec::new();
+        let mut finish_piece = |hasher: &mut Sha1, tainted: &mut bool, touched: &mut Vec<usize>| {
+            let good = hasher.finalize_reset()[..] == self.pieces[piece];
+            for &i in &*touched {
+                if *tainted {
+                    unverified[i] += 1;
+                    continue;
+                }
+                verified[i] += 1;
+                if !good {
+                    failed[i].push(piece);
+                }
+            }
+            piece += 1;
+            *tainted = false;
+            touched.clear();
+        };
+
+        for (i, (_, len)) in self.files.iter().enumerate() {
+            let mut left = *len;
+            while left > 0 {
+                let n = left.min(self.piece_len - filled);
+                match &mut readers[i] {
+                    Some(file) => {
+                        if io::copy(&mut file.take(n), &mut hasher)? != n {
+                            tainted = true;
+                        }
+                    }
+                    None => tainted = true,
+                }
+                if touched.last() != Some(&i) {
+                    touched.push(i);
+                }
+                filled += n;
+                left -= n;
+                if filled == self.piece_len {
+                    finish_piece(&mut hasher, &mut tainted, &mut touched);
+                    filled = 0;
+                }
+            }
+        }
+        if filled != 0 {
+            finish_piece(&mut hasher, &mut tainted, &mut touched);
+        }
+
+        for (i, (name, _)) in self.files.iter().enumerate() {
+            if readers[i].is_none() {
+                continue;
+            }
+            let verdict = if !failed[i].is_empty() {
+                Verdict::Bad {
+                    failed: mem::take(&mut failed[i]),
+                    verified: verified[i],
+                }
+            } else if unverified[i] != 0 {
+                Verdict::Unverified(unverified[i])
+            } else {
+                Verdict::Good
+            };
+            verdicts.insert(name.clone(), verdict);
+        }
+        Ok(verdicts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn malformed_bencode_is_error() {
+        let deep = "l".repeat(100_000);
+        let cases: &[&[u8]] = &[
+            b"",
+            b"i12",
+            b"ie",
+            b"i-e",
+            b"5:abc",
+            b"99999999999999999999999:a",
+            b"l",
+            b"li1e",
+            b"d1:ae",
+            b"di1ei2ee",
+            b"x",
+            deep.as_bytes(),
+        ];
+        for case in cases {
+            let mut s = *case;
+            assert!(
+                decode(&mut s, 0).is_err(),
+                "{}",
+                String::from_utf8_lossy(case)
+            );
+        }
+    }
+
+    #[test]
+    fn decodes_nested_values() {
+        let mut s = &b"d1:ali1ei-2ee1:b3:xyze"[..];
+        let value = decode(&mut s, 0).unwrap();
+        assert!(s.is_empty());
+        let list = value.get("a").unwrap().list().unwrap();
+        assert_eq!(list[0].int().unwrap(), 1);
+        assert!(list[1].int().is_err());
+        assert_eq!(value.get("b").unwrap().bytes().unwrap(), b"xyz");
+        assert!(value.get("c").is_err());
+    }
+}
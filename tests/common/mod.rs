@@ -0,0 +1,89 @@
+#![allow(dead_code)]
+
+use std::{
+    env, fs,
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    process,
+    sync::Arc,
+    thread,
+};
+
+/// Serves HTTP on a local port with the response `respond` gives to each request head,
+/// returning the base URL.
+pub fn serve(respond: impl Fn(&str) -> Vec<u8> + Send + Sync + 'static) -> String {
+    serve_with(move |head, stream| {
+        let _ = stream.write_all(&respond(head));
+    })
+}
+
+/// Serves HTTP on a local port, handing each connection to `handle` along with its
+/// request head, and returning the base URL.
+pub fn serve_with(handle: impl Fn(&str, &mut TcpStream) + Send + Sync + 'static) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handle = Arc::new(handle);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let handle = handle.clone();
+            thread::spawn(move || {
+                let mut head = Vec::new();
+                let mut byte = [0];
+                while !head.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap_or(0) == 1 {
+                    head.push(byte[0]);
+                }
+                handle(&String::from_utf8_lossy(&head), &mut stream);
+            });
+        }
+    });
+    format!("http://{addr}")
+}
+
+/// Builds a response with the given status line, extra headers and body.
+pub fn response(status: &str, headers: &[&str], body: &[u8]) -> Vec<u8> {
+    let mut res = format!("HTTP/1.1 {status}\r\nContent-Length: {}\r\n", body.len());
+    for header in headers {
+        res += header;
+        res += "\r\n";
+    }
+    res += "Connection: close\r\n\r\n";
+    let mut res = res.into_bytes();
+    res.extend_from_slice(body);
+    res
+}
+
+/// Creates an empty directory for a test.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("howis-test-{}-{name}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs a check with the given arguments, returning its report and output.
+pub fn check(args: &[&str]) -> (howis::RunReport, String) {
+    try_check(args).unwrap_or_else(|e| panic!("run failed: {e:#}"))
+}
+
+/// Runs a check with the given arguments, returning its report and output if it succeeds.
+pub fn try_check(args: &[&str]) -> anyhow::Result<(howis::RunReport, String)> {
+    let matches = howis::command()
+        .try_get_matches_from(["howis"].iter().chain(args))
+        .unwrap();
+    let config = howis::Config::from_matches(&matches);
+    let mut out = Vec::new();
+    let report = howis::run(config, &mut out)?;
+    Ok((report, String::from_utf8(out).unwrap()))
+}
+
+/// Returns the status recorded for a name in a run.
+pub fn status<'a>(report: &'a howis::RunReport, name: &str) -> &'a str {
+    let (_, status) = report
+        .outcomes
+        .iter()
+        .rfind(|(n, _)| n == name)
+        .unwrap_or_else(|| panic!("{name} not recorded"));
+    status
+}
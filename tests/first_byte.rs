@@ -0,0 +1,52 @@
+mod common;
+
+use std::{fs, io::Write, thread, time::Duration};
+
+use common::{check, serve_with, status, temp_dir};
+
+#[test]
+fn silent_server_is_no_data() {
+    // Accepts the request but never responds.
+    let base = serve_with(|_, _| thread::sleep(Duration::from_secs(10)));
+    let dir = temp_dir("silent_server_is_no_data");
+    let file = dir.join("a.txt");
+    fs::write(&file, "content").unwrap();
+
+    let rec = dir.join("rec.txt");
+    let (report, _) = check(&[
+        "-s",
+        &format!("{base}/{{}}"),
+        "-r",
+        rec.to_str().unwrap(),
+        "--first-byte-timeout",
+        "1",
+        file.to_str().unwrap(),
+    ]);
+    assert_eq!(status(&report, "a.txt"), "error: no data");
+}
+
+#[test]
+fn stalled_response_is_not_aborted() {
+    // Stops for longer than the timeout after the response has started.
+    let base = serve_with(|_, stream| {
+        let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 7\r\n\r\ncon");
+        let _ = stream.flush();
+        thread::sleep(Duration::from_secs(2));
+        let _ = stream.write_all(b"tent");
+    });
+    let dir = temp_dir("stalled_response_is_not_aborted");
+    let file = dir.join("a.txt");
+    fs::write(&file, "content").unwrap();
+
+    let rec = dir.join("rec.txt");
+    let (report, _) = check(&[
+        "-s",
+        &format!("{base}/{{}}"),
+        "-r",
+        rec.to_str().unwrap(),
+        "--first-byte-timeout",
+        "1",
+        file.to_str().unwrap(),
+    ]);
+    assert_eq!(status(&report, "a.txt"), "good");
+}
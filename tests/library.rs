@@ -0,0 +1,77 @@
+mod common;
+
+use std::fs;
+
+use common::{response, serve, temp_dir};
+
+#[test]
+fn builds_config_and_reports() {
+    let base = serve(|_| response("200 OK", &[], b"content"));
+    let dir = temp_dir("builds_config_and_reports");
+    let file = dir.join("a.txt");
+    fs::write(&file, "content").unwrap();
+
+    let config = howis::Config::builder()
+        .src(format!("{base}/{{}}"))
+        .rec(dir.join("rec.txt"))
+        .option("retry-on-bad", "1")
+        .flag("explain")
+        .file(&file)
+        .build()
+        .unwrap();
+    let mut out = Vec::new();
+    let report = howis::run(config, &mut out).unwrap();
+    assert_eq!(report.counter.good, 1);
+    assert_eq!(report.bytes(), 7);
+    assert_eq!(report.recorded_at().len(), 1);
+    assert!(!report.error_limit_reached());
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains(&format!("run: {}", report.run_id())));
+    assert!(out.contains("  compared: 7 byte(s) received, 7 local"));
+}
+
+#[test]
+fn builder_validates_options() {
+    let build = |builder: howis::ConfigBuilder| builder.rec("rec.txt").file("a.txt").build();
+    assert!(build(howis::Config::builder().src("list.txt")).is_ok());
+    assert!(build(howis::Config::builder().option("retry-on-bad", "many")).is_err());
+    assert!(build(howis::Config::builder().flag("no-such-flag")).is_err());
+    // Files named like the subcommand are still files.
+    let config = howis::Config::builder()
+        .src("list.txt")
+        .rec("rec.txt")
+        .file("diff")
+        .build();
+    assert!(config.is_ok());
+}
+
+#[test]
+fn logs_runs_in_one_process() {
+    let base = serve(|_| response("200 OK", &[], b"content"));
+    let dir = temp_dir("logs_runs_in_one_process");
+    let file = dir.join("a.txt");
+    fs::write(&file, "content").unwrap();
+    let logs = [dir.join("first.log"), dir.join("second.log")];
+
+    for log in &logs {
+        let config = howis::Config::builder()
+            .src(format!("{base}/{{}}"))
+            .rec(dir.join(log.with_extension("rec")))
+            .option("log-file", log)
+            .file(&file)
+            .build()
+            .unwrap();
+        let report = howis::run(config, &mut Vec::new()).unwrap();
+        let log = fs::read_to_string(log).unwrap();
+        assert!(log.contains(&format!("started run {}", report.run_id())));
+        assert!(log.contains("a.txt: good"));
+    }
+    // Entries of the second run go to its own log.
+    assert_eq!(
+        fs::read_to_string(&logs[0])
+            .unwrap()
+            .matches("started run")
+            .count(),
+        1
+    );
+}
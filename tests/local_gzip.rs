@@ -0,0 +1,53 @@
+mod common;
+
+use std::{fs, io::Write};
+
+use common::{check, response, serve, status, temp_dir};
+use flate2::{write::GzEncoder, Compression};
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Checks a local file with `--local-gzip` against a remote one, returning its status.
+fn check_gzip(test: &str, local: &[u8]) -> String {
+    let base = serve(|_| response("200 OK", &[], b"remote content"));
+    let dir = temp_dir(test);
+    let file = dir.join("a.gz");
+    fs::write(&file, local).unwrap();
+
+    let rec = dir.join("rec.txt");
+    let (report, _) = check(&[
+        "-s",
+        &format!("{base}/{{}}"),
+        "-r",
+        rec.to_str().unwrap(),
+        "--local-gzip",
+        file.to_str().unwrap(),
+    ]);
+    status(&report, "a.gz").into()
+}
+
+#[test]
+fn matching_gzip_is_good() {
+    assert_eq!(
+        check_gzip("matching_gzip_is_good", &gzip(b"remote content")),
+        "good"
+    );
+}
+
+#[test]
+fn decompressed_length_must_match() {
+    let local = gzip(b"remote content, and more");
+    assert_eq!(check_gzip("decompressed_length_must_match", &local), "bad");
+}
+
+#[test]
+fn corrupt_gzip_is_bad() {
+    let mut local = gzip(b"remote content");
+    // Damaging the compressed data fails its checksum if not the decompression itself.
+    local[12] ^= 0xff;
+    assert_eq!(check_gzip("corrupt_gzip_is_bad", &local), "bad");
+}
@@ -0,0 +1,114 @@
+mod common;
+
+use std::fs;
+
+use common::{check, response, serve, status, temp_dir};
+use sha2::{Digest, Sha256};
+
+/// Writes a Metalink of files with the given content, listing a mirror that refuses
+/// connections before the server at `base`.
+fn write_metalink(path: &std::path::Path, base: &str, files: &[(&str, &[u8])]) {
+    let mut xml = String::from(r#"<metalink xmlns="urn:ietf:params:xml:ns:metalink">"#);
+    for (name, content) in files {
+        let digest: String = Sha256::digest(content)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        xml += &format!(
+            r#"<file name="{name}"><hash type="sha-256">{digest}</hash>
+            <url priority="2">{base}/{name}</url>
+            <url priority="1">http://127.0.0.1:1/{name}</url></file>"#
+        );
+    }
+    xml += "</metalink>";
+    fs::write(path, xml).unwrap();
+}
+
+#[test]
+fn falls_back_on_mirrors() {
+    let base = serve(|_| response("200 OK", &[], b"content"));
+    let dir = temp_dir("falls_back_on_mirrors");
+    let file = dir.join("a.txt");
+    fs::write(&file, "content").unwrap();
+    let meta = dir.join("files.meta4");
+    write_metalink(
+        &meta,
+        &base,
+        &[("a.txt", b"content"), ("b.txt", b"content")],
+    );
+
+    let rec = dir.join("rec.txt");
+    let (report, _) = check(&[
+        "-s",
+        meta.to_str().unwrap(),
+        "-r",
+        rec.to_str().unwrap(),
+        file.to_str().unwrap(),
+    ]);
+    assert_eq!(status(&report, "a.txt"), "good");
+    // The rest of the list is probed through the mirrors as well.
+    assert!(status(&report, "b.txt").starts_with("error: available"));
+}
+
+#[test]
+fn checks_digests_locally() {
+    let base = serve(|_| response("200 OK", &[], b"content"));
+    let dir = temp_dir("checks_digests_locally");
+    let file = dir.join("a.txt");
+    fs::write(&file, "changed").unwrap();
+    let meta = dir.join("files.meta4");
+    write_metalink(&meta, &base, &[("a.txt", b"content")]);
+
+    let rec = dir.join("rec.txt");
+    let (report, _) = check(&[
+        "-s",
+        meta.to_str().unwrap(),
+        "-r",
+        rec.to_str().unwrap(),
+        file.to_str().unwrap(),
+    ]);
+    assert_eq!(
+        status(&report, "a.txt"),
+        "bad: content does not match digest"
+    );
+}
+
+#[test]
+fn ranks_mirrors_by_past_transfers() {
+    let base = serve(|_| response("200 OK", &[], b"content"));
+    let dir = temp_dir("ranks_mirrors_by_past_transfers");
+    let file = dir.join("a.txt");
+    fs::write(&file, "content").unwrap();
+    let meta = dir.join("files.meta4");
+    write_metalink(&meta, &base, &[("a.txt", b"content")]);
+    let stats = dir.join("mirrors.txt");
+    let run = |rec: &str, reset: bool| {
+        let rec = dir.join(rec);
+        let mut args = vec![
+            "-s",
+            meta.to_str().unwrap(),
+            "-r",
+            rec.to_str().unwrap(),
+            "--mirror-rank",
+            stats.to_str().unwrap(),
+        ];
+        if reset {
+            args.push("--reset-mirror-stats");
+        }
+        args.push(file.to_str().unwrap());
+        let (report, out) = check(&args);
+        assert_eq!(status(&report, "a.txt"), "good");
+        out
+    };
+
+    // The refusing mirror comes first in priority until it has failed.
+    assert!(run("rec1.txt", false).contains("trying the next mirror"));
+    let saved = fs::read_to_string(&stats).unwrap();
+    assert!(
+        saved.contains("http://127.0.0.1:1 0 1 0 0.000\n"),
+        "{saved}"
+    );
+    assert!(saved.contains(&format!("{base} 1 0 7 ")), "{saved}");
+    assert!(!run("rec2.txt", false).contains("trying the next mirror"));
+    assert!(run("rec3.txt", true).contains("trying the next mirror"));
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use std::fs;
+
+use common::{check, response, serve, status, temp_dir};
+
+#[test]
+fn unsolicited_partial_content_is_error() {
+    // Only the first half of the file, as if a range had been requested.
+    let base = serve(|_| {
+        response(
+            "206 Partial Content",
+            &["Content-Range: bytes 0-3/8"],
+            b"cont",
+        )
+    });
+    let dir = temp_dir("unsolicited_partial_content_is_error");
+    let file = dir.join("a.txt");
+    fs::write(&file, "cont").unwrap();
+
+    let rec = dir.join("rec.txt");
+    let (report, _) = check(&[
+        "-s",
+        &format!("{base}/{{}}"),
+        "-r",
+        rec.to_str().unwrap(),
+        file.to_str().unwrap(),
+    ]);
+    assert_eq!(
+        status(&report, "a.txt"),
+        "error: unexpected partial content"
+    );
+}
+
+/// Serves a 416 to every range request, as for a remote file shorter than any range.
+fn serve_unsatisfiable() -> String {
+    serve(|head| {
+        if head.to_ascii_lowercase().contains("\r\nrange:") {
+            response(
+                "416 Range Not Satisfiable",
+                &["Content-Range: bytes */4"],
+                b"",
+            )
+        } else {
+            response("200 OK", &[], b"cont")
+        }
+    })
+}
+
+#[test]
+fn force_ranged_past_end_is_bad() {
+    let base = serve_unsatisfiable();
+    let dir = temp_dir("force_ranged_past_end_is_bad");
+    let file = dir.join("a.txt");
+    fs::write(&file, "content").unwrap();
+
+    let rec = dir.join("rec.txt");
+    let (report, _) = check(&[
+        "-s",
+        &format!("{base}/{{}}"),
+        "-r",
+        rec.to_str().unwrap(),
+        "--force-ranged",
+        "4",
+        file.to_str().unwrap(),
+    ]);
+    assert_eq!(status(&report, "a.txt"), "bad: too short for range");
+}
+
+#[test]
+fn slice_past_end_is_bad() {
+    let base = serve_unsatisfiable();
+    let dir = temp_dir("slice_past_end_is_bad");
+    let file = dir.join("a.txt");
+    fs::write(&file, "content").unwrap();
+    let list = dir.join("list.txt");
+    fs::write(&list, format!("a.txt {base}/remote@100+7\n")).unwrap();
+
+    let rec = dir.join("rec.txt");
+    let (report, _) = check(&[
+        "-s",
+        list.to_str().unwrap(),
+        "-r",
+        rec.to_str().unwrap(),
+        file.to_str().unwrap(),
+    ]);
+    assert_eq!(status(&report, "a.txt"), "bad: too short for range");
+}
+
+/// Checks `a.txt` against `base` in the region of its range map.
+fn check_range_map(dir: &std::path::Path, base: &str) -> howis::RunReport {
+    let file = dir.join("a.txt");
+    fs::write(&file, "content").unwrap();
+    let map = dir.join("map.txt");
+    fs::write(&map, "a.txt 0-3\n").unwrap();
+
+    let rec = dir.join("rec.txt");
+    let (report, _) = check(&[
+        "-s",
+        &format!("{base}/{{}}"),
+        "-r",
+        rec.to_str().unwrap(),
+        "--range-map",
+        map.to_str().unwrap(),
+        file.to_str().unwrap(),
+    ]);
+    report
+}
+
+#[test]
+fn range_map_unserved_is_error() {
+    let base = serve(|_| response("200 OK", &[], b"content"));
+    let dir = temp_dir("range_map_unserved_is_error");
+    let report = check_range_map(&dir, &base);
+    assert_eq!(status(&report, "a.txt"), "error: range not served");
+    assert_eq!((report.counter.error, report.counter.stale), (1, 0));
+}
+
+#[test]
+fn range_map_connection_error_is_error() {
+    // Nothing listens on the port.
+    let dir = temp_dir("range_map_connection_error_is_error");
+    let report = check_range_map(&dir, "http://127.0.0.1:1");
+    assert!(status(&report, "a.txt").starts_with("error: connect: "));
+    assert_eq!((report.counter.error, report.counter.stale), (1, 0));
+}
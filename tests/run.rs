@@ -0,0 +1,278 @@
+mod common;
+
+use std::{fs, io::Write};
+
+use common::{check, response, serve, status, temp_dir, try_check};
+
+#[test]
+fn compares_with_source() {
+    let base = serve(|_| response("200 OK", &[], b"remote content"));
+    let dir = temp_dir("compares_with_source");
+    let good = dir.join("good.txt");
+    let bad = dir.join("bad.txt");
+    fs::write(&good, "remote content").unwrap();
+    fs::write(&bad, "local content").unwrap();
+
+    let rec = dir.join("rec.txt");
+    let (report, _) = check(&[
+        "-s",
+        &format!("{base}/{{}}"),
+        "-r",
+        rec.to_str().unwrap(),
+        good.to_str().unwrap(),
+        bad.to_str().unwrap(),
+    ]);
+    assert!(status(&report, "good.txt").starts_with("good"));
+    assert!(status(&report, "bad.txt").starts_with("bad"));
+    assert_eq!((report.counter.good, report.counter.bad), (1, 1));
+}
+
+#[test]
+fn template_keeps_name_case() {
+    let base = serve(|head| {
+        if head.starts_with("GET /Setup.EXE ") {
+            response("200 OK", &[], b"installer")
+        } else {
+            response("404 Not Found", &[], b"")
+        }
+    });
+    let dir = temp_dir("template_keeps_name_case");
+    let file = dir.join("Setup.EXE");
+    fs::write(&file, "installer").unwrap();
+
+    let rec = dir.join("rec.txt");
+    let (report, _) = check(&[
+        "-s",
+        &format!("{base}/{{}}"),
+        "-r",
+        rec.to_str().unwrap(),
+        "--ignore-name-case",
+        file.to_str().unwrap(),
+    ]);
+    assert!(status(&report, "Setup.EXE").starts_with("good"));
+}
+
+#[test]
+fn rec_offset_remembers_names() {
+    let base = serve(|_| response("200 OK", &[], b"content"));
+    let dir = temp_dir("rec_offset_remembers_names");
+    let file = dir.join("a.txt");
+    fs::write(&file, "content").unwrap();
+    let list = dir.join("list.txt");
+    fs::write(&list, format!("{base}/a.txt\n{base}/b.txt\n")).unwrap();
+
+    let rec = dir.join("rec.txt");
+    let args = [
+        "-s",
+        list.to_str().unwrap(),
+        "-r",
+        rec.to_str().unwrap(),
+        "--rec-offset",
+        file.to_str().unwrap(),
+    ];
+    let (first, _) = check(&args);
+    assert_eq!(first.outcomes.len(), 2);
+
+    // Neither the file nor the rest of the URL list is checked again.
+    let (second, _) = check(&args);
+    assert!(second.outcomes.is_empty());
+    assert_eq!(second.counter.total(), first.counter.total());
+
+    // Only the new names are appended, over what an interrupted save left behind.
+    let keys = dir.join("rec.txt.keys");
+    assert_eq!(fs::read_to_string(&keys).unwrap().lines().count(), 2);
+    fs::OpenOptions::new()
+        .append(true)
+        .open(&keys)
+        .unwrap()
+        .write_all(b"0123")
+        .unwrap();
+    let other = dir.join("c.txt");
+    fs::write(&other, "content").unwrap();
+    let (third, _) = check(&[&args[..], &[other.to_str().unwrap()]].concat());
+    assert_eq!(third.outcomes.len(), 1);
+    let keys = fs::read_to_string(&keys).unwrap();
+    assert_eq!(keys.lines().count(), 3);
+    assert!(keys.lines().all(|line| {
+        let (hash, _) = line.split_once(' ').unwrap();
+        hash.len() == 16 && hash.bytes().all(|b| b.is_ascii_hexdigit())
+    }));
+}
+
+#[test]
+fn fail_on_missing_source_requires_source() {
+    let cli = |args: &[&str]| howis::command().try_get_matches_from(["howis"].iter().chain(args));
+    assert!(cli(&["--fail-on-missing-source", "--remote-dir", ".", "a.txt"]).is_err());
+    assert!(cli(&["--fail-on-missing-source", "--cas", "a.txt"]).is_err());
+    assert!(cli(&["--fail-on-missing-source", "-s", "list.txt", "a.txt"]).is_ok());
+}
+
+#[test]
+fn probes_decode_names_only_when_normalizing() {
+    // The file is served at a percent-encoded form of its name.
+    let base = serve(|head| {
+        if head.starts_with("HEAD /a.txt ") {
+            response("302 Found", &["Location: /%61.txt"], b"")
+        } else {
+            response("200 OK", &[], b"content")
+        }
+    });
+    let dir = temp_dir("probes_decode_names_only_when_normalizing");
+    let list = dir.join("list.txt");
+    fs::write(&list, format!("{base}/a.txt\n{base}/b.txt\n")).unwrap();
+    let file = dir.join("b.txt");
+    fs::write(&file, "content").unwrap();
+
+    let run = |extra: &[&str]| {
+        let rec = dir.join("rec.txt");
+        let _ = fs::remove_file(&rec);
+        let mut args = vec![
+            "-s",
+            list.to_str().unwrap(),
+            "-r",
+            rec.to_str().unwrap(),
+            file.to_str().unwrap(),
+        ];
+        args.extend(extra);
+        let (report, _) = check(&args);
+        status(&report, "a.txt").to_owned()
+    };
+    assert_eq!(run(&[]), "n/a");
+    assert!(run(&["--normalize-names"]).starts_with("error: available"));
+}
+
+#[test]
+fn pause_rejects_source_from_stdin() {
+    let dir = temp_dir("pause_rejects_source_from_stdin");
+    let rec = dir.join("rec.txt");
+    let Err(e) = try_check(&[
+        "-s",
+        "-",
+        "-r",
+        rec.to_str().unwrap(),
+        "--pause-before-availability",
+        "a.txt",
+    ]) else {
+        panic!("run succeeded");
+    };
+    assert!(e.to_string().starts_with("--pause-before-availability"));
+    assert!(!rec.exists());
+}
+
+#[test]
+fn list_hosts_needs_no_files() {
+    let dir = temp_dir("list_hosts_needs_no_files");
+    let list = dir.join("list.txt");
+    fs::write(&list, "http://a.example/x.zip\nhttp://b.example/y.zip\n").unwrap();
+    let matches = howis::command()
+        .try_get_matches_from(["howis", "-s", list.to_str().unwrap(), "--list-hosts"])
+        .unwrap();
+    howis::cli(&matches).unwrap();
+}
+
+#[test]
+fn src_api_cache_is_kept_to_its_url() {
+    let base = serve(|head| match head.split(' ').nth(1) {
+        Some("/list.json") | Some("/other.json") => response("200 OK", &[], b"[]"),
+        _ => response("200 OK", &[], b"content"),
+    });
+    let dir = temp_dir("src_api_cache_is_kept_to_its_url");
+    let file = dir.join("a.txt");
+    fs::write(&file, "content").unwrap();
+    let cache = dir.join("cache.json");
+
+    let run = |api: &str| {
+        let rec = dir.join("rec.txt");
+        let _ = fs::remove_file(&rec);
+        try_check(&[
+            "--src-api",
+            &format!("{base}/{api}"),
+            "--src-api-cache",
+            cache.to_str().unwrap(),
+            "-r",
+            rec.to_str().unwrap(),
+            file.to_str().unwrap(),
+        ])
+    };
+    run("list.json").unwrap();
+    // Resumed against the cached response.
+    run("list.json").unwrap();
+    let Err(e) = run("other.json") else {
+        panic!("a cache of another URL was used");
+    };
+    assert!(format!("{e:#}").contains("list.json"));
+}
+
+#[test]
+fn failed_prewarm_records_session_errors() {
+    let base = serve(|head| {
+        if head.starts_with("GET /consent ") {
+            response("500 Internal Server Error", &[], b"")
+        } else {
+            response("200 OK", &[], b"content")
+        }
+    });
+    let dir = temp_dir("failed_prewarm_records_session_errors");
+    let file = dir.join("a.txt");
+    fs::write(&file, "content").unwrap();
+
+    let rec = dir.join("rec.txt");
+    let (report, _) = check(&[
+        "-s",
+        &format!("{base}/{{}}"),
+        "-r",
+        rec.to_str().unwrap(),
+        "--prewarm-url",
+        &format!("{base}/consent"),
+        file.to_str().unwrap(),
+    ]);
+    assert_eq!(status(&report, "a.txt"), "error: session");
+    assert_eq!(report.counter.error, 1);
+    assert_eq!(fs::read_to_string(&rec).unwrap(), "a.txt: error: session\n");
+}
+
+#[test]
+fn session_refuses_changed_options() {
+    let base = serve(|_| response("200 OK", &[], b"content"));
+    let dir = temp_dir("session_refuses_changed_options");
+    let file = dir.join("a.txt");
+    fs::write(&file, "content").unwrap();
+    let session = dir.join("session.txt");
+    let rec = dir.join("rec.txt");
+    let src = format!("{base}/{{}}");
+
+    let run = |extra: &[&str]| {
+        let mut args = vec![
+            "howis",
+            "-s",
+            &src,
+            "-r",
+            rec.to_str().unwrap(),
+            "--session",
+            session.to_str().unwrap(),
+            file.to_str().unwrap(),
+        ];
+        args.extend(extra);
+        howis::cli(&howis::command().try_get_matches_from(args).unwrap())
+    };
+    run(&[]).unwrap();
+    let saved = fs::read_to_string(&session).unwrap();
+    assert!(saved.contains("runs: 1\n"));
+    assert!(saved.contains("good: 1\n"));
+    assert!(saved.contains("last: a.txt\n"));
+
+    run(&[]).unwrap();
+    assert!(fs::read_to_string(&session).unwrap().contains("runs: 2\n"));
+    for changed in [
+        &["--retry-on-bad", "1"][..],
+        &["--dedup"],
+        &["--use-etags", "etags.txt"],
+    ] {
+        let e = run(changed).unwrap_err();
+        assert!(e.to_string().starts_with(changed[0]), "{e}");
+    }
+
+    // A record rewritten under the session is refused too.
+    fs::write(&rec, "").unwrap();
+    assert!(run(&[]).unwrap_err().to_string().contains("shorter"));
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use std::{fs, path::Path};
+
+use common::{check, status, temp_dir, try_check};
+use sha1::{Digest, Sha1};
+
+/// Bencodes a byte string.
+fn bytes(s: &[u8]) -> Vec<u8> {
+    let mut res = format!("{}:", s.len()).into_bytes();
+    res.extend_from_slice(s);
+    res
+}
+
+/// Writes a multi-file torrent of the given files with 4-byte pieces.
+fn write_torrent(path: &Path, files: &[(&str, &[u8])]) {
+    let content: Vec<u8> = files.iter().flat_map(|(_, data)| data.to_vec()).collect();
+    let pieces: Vec<u8> = content.chunks(4).flat_map(Sha1::digest).collect();
+    let mut torrent = b"d4:infod5:filesl".to_vec();
+    for (name, data) in files {
+        torrent.extend(format!("d6:lengthi{}e4:pathl", data.len()).bytes());
+        torrent.extend(bytes(name.as_bytes()));
+        torrent.extend(b"ee");
+    }
+    torrent.extend(b"e4:name");
+    torrent.extend(bytes(b"dir"));
+    torrent.extend(b"12:piece lengthi4e6:pieces");
+    torrent.extend(bytes(&pieces));
+    torrent.extend(b"ee");
+    fs::write(path, torrent).unwrap();
+}
+
+/// Checks the local files offline against a torrent of `a.txt` and `b.txt`,
+/// whose second piece spans both files.
+fn check_torrent(name: &str, locals: &[(&str, &[u8])]) -> howis::RunReport {
+    check_torrent_with(name, &[], locals)
+}
+
+/// Checks the local files as with `check_torrent`, with extra options.
+fn check_torrent_with(name: &str, options: &[&str], locals: &[(&str, &[u8])]) -> howis::RunReport {
+    let dir = temp_dir(name);
+    let torrent = dir.join("dir.torrent");
+    write_torrent(&torrent, &[("a.txt", b"abcdef"), ("b.txt", b"ghij")]);
+    let mut args = vec![
+        "--torrent".to_string(),
+        torrent.to_str().unwrap().into(),
+        "-r".into(),
+        dir.join("rec.txt").to_str().unwrap().into(),
+    ];
+    args.extend(options.iter().map(|s| s.to_string()));
+    for (name, data) in locals {
+        let path = dir.join(name);
+        fs::write(&path, data).unwrap();
+        args.push(path.to_str().unwrap().into());
+    }
+    let args: Vec<_> = args.iter().map(String::as_str).collect();
+    check(&args).0
+}
+
+#[test]
+fn shared_piece_verifies_both_files() {
+    let report = check_torrent(
+        "shared_piece_verifies_both_files",
+        &[("a.txt", b"abcdef"), ("b.txt", b"ghij")],
+    );
+    assert_eq!(status(&report, "a.txt"), "good: pieces match torrent");
+    assert_eq!(status(&report, "b.txt"), "good: pieces match torrent");
+}
+
+#[test]
+fn shared_piece_fails_both_files() {
+    let report = check_torrent(
+        "shared_piece_fails_both_files",
+        &[("a.txt", b"abcdef"), ("b.txt", b"Xhij")],
+    );
+    assert_eq!(status(&report, "a.txt"), "bad: failed piece(s): 1");
+    assert_eq!(status(&report, "b.txt"), "bad: failed piece(s): 1");
+}
+
+#[test]
+fn partial_credit_counts_chunks() {
+    let locals: &[(&str, &[u8])] = &[("a.txt", b"Xbcdef"), ("b.txt", b"ghij")];
+    let report = check_torrent_with(
+        "partial_credit_counts_chunks",
+        &["--partial-credit"],
+        locals,
+    );
+    assert_eq!(status(&report, "a.txt"), "bad: 1/2 chunks failed");
+    assert_eq!(status(&report, "b.txt"), "good: pieces match torrent");
+
+    let options = ["--partial-credit", "--accept-threshold", "0.5"];
+    let report = check_torrent_with("partial_credit_accepts_chunks", &options, locals);
+    assert_eq!(status(&report, "a.txt"), "good: 1/2 chunks match");
+    assert_eq!(report.counter.good, 2);
+}
+
+#[test]
+fn missing_neighbour_is_error() {
+    let report = check_torrent("missing_neighbour_is_error", &[("b.txt", b"ghij")]);
+    assert_eq!(
+        status(&report, "b.txt"),
+        "error: 1 piece(s) span missing files"
+    );
+    assert_eq!(report.counter.error, 1);
+}
+
+#[test]
+fn length_mismatch_is_bad() {
+    let report = check_torrent(
+        "length_mismatch_is_bad",
+        &[("a.txt", b"abcde"), ("b.txt", b"ghij")],
+    );
+    assert_eq!(status(&report, "a.txt"), "bad: length differs from torrent");
+    // The shared piece can't be checked without the right neighbour.
+    assert_eq!(
+        status(&report, "b.txt"),
+        "error: 1 piece(s) span missing files"
+    );
+}
+
+#[test]
+fn malformed_torrent_is_error() {
+    let dir = temp_dir("malformed_torrent_is_error");
+    let torrent = dir.join("dir.torrent");
+    fs::write(&torrent, "d4:infod12:piece lengthi4e6:pieces").unwrap();
+    let file = dir.join("a.txt");
+    fs::write(&file, "abcd").unwrap();
+    let rec = dir.join("rec.txt");
+    let Err(e) = try_check(&[
+        "--torrent",
+        torrent.to_str().unwrap(),
+        "-r",
+        rec.to_str().unwrap(),
+        file.to_str().unwrap(),
+    ]) else {
+        panic!("a malformed torrent was loaded");
+    };
+    assert!(e.to_string().contains("torrent"));
+}